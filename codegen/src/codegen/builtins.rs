@@ -0,0 +1,798 @@
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
+use ::error::{current_file, LoaError, LoaErrorKind, RuntimeErrorKind};
+use crate::{Interpreter, LazySeq, LazyStage, Value};
+use parser::ast::FormatPart;
+use parser::parse_format_string;
+use crate::codegen::codegen::{is_truthy, map_get, MAX_DISPLAY_DEPTH};
+
+// Signature and one-line description of a built-in, shown by the REPL's `.help`
+pub struct BuiltinInfo {
+    pub name: &'static str,
+    pub signature: &'static str,
+    pub description: &'static str,
+}
+
+pub const BUILTINS: &[BuiltinInfo] = &[
+    BuiltinInfo { name: "len", signature: "len(x)", description: "Characters in a string, elements in an array or entries in a map" },
+    BuiltinInfo { name: "push", signature: "push(arr, x)", description: "A copy of an array with x appended" },
+    BuiltinInfo { name: "display_len", signature: "display_len(s)", description: "Number of grapheme clusters in a string" },
+    BuiltinInfo { name: "seed", signature: "seed(n)", description: "Reseed the random generator for reproducible runs" },
+    BuiltinInfo { name: "random", signature: "random()", description: "A random float in [0, 1)" },
+    BuiltinInfo { name: "shuffle", signature: "shuffle(arr)", description: "A randomly permuted copy of an array" },
+    BuiltinInfo { name: "div_round", signature: "div_round(a, b, mode)", description: "Integer division rounded by \"floor\", \"ceil\", \"half_up\" or \"trunc\"" },
+    BuiltinInfo { name: "timeit", signature: "timeit(f, n)", description: "Call f() n times and return the average seconds per call" },
+    BuiltinInfo { name: "pretty", signature: "pretty(x)", description: "Nested arrays and maps laid out one element per line" },
+    BuiltinInfo { name: "abs", signature: "abs(x)", description: "Absolute value of a number" },
+    BuiltinInfo { name: "min", signature: "min(x, ...)", description: "Smallest argument; a float if any argument is a float" },
+    BuiltinInfo { name: "max", signature: "max(x, ...)", description: "Largest argument; a float if any argument is a float" },
+    BuiltinInfo { name: "input", signature: "input(prompt)", description: "Print the optional prompt and read one line from stdin" },
+    BuiltinInfo { name: "int", signature: "int(x)", description: "Parse a string as an integer, or truncate a float toward zero" },
+    BuiltinInfo { name: "float", signature: "float(x)", description: "Parse a string as a float, or widen an integer" },
+    BuiltinInfo { name: "type", signature: "type(x)", description: "The type of a value as a string, e.g. \"number\" or \"array\"" },
+    BuiltinInfo { name: "str", signature: "str(x)", description: "Any value as a string, formatted as print shows it" },
+    BuiltinInfo { name: "upper", signature: "upper(s)", description: "A copy of a string in upper case" },
+    BuiltinInfo { name: "lower", signature: "lower(s)", description: "A copy of a string in lower case" },
+    BuiltinInfo { name: "trim", signature: "trim(s)", description: "A copy of a string without leading and trailing whitespace" },
+    BuiltinInfo { name: "format", signature: "format(template, x, ...)", description: "The template with each {} replaced by the next value as print shows it; {{ and }} are literal braces" },
+    BuiltinInfo { name: "range", signature: "range(start, end, step)", description: "Integers from start up to end by the optional step, as an array; a negative step counts down" },
+    BuiltinInfo { name: "split", signature: "split(s, delim)", description: "The pieces of a string between delimiters; an empty delimiter gives characters" },
+    BuiltinInfo { name: "join", signature: "join(arr, sep)", description: "Array elements as print shows them, joined by sep" },
+    BuiltinInfo { name: "contains", signature: "contains(x, item)", description: "Whether a string has a substring or an array has an equal element" },
+    BuiltinInfo { name: "index_of", signature: "index_of(x, item)", description: "Position of the first substring or equal element, or -1" },
+    BuiltinInfo { name: "keys", signature: "keys(m)", description: "The keys of a map, as an array in insertion order" },
+    BuiltinInfo { name: "values", signature: "values(m)", description: "The values of a map, as an array in insertion order" },
+    BuiltinInfo { name: "has", signature: "has(m, key)", description: "Whether a map contains a key" },
+    BuiltinInfo { name: "lazy_range", signature: "lazy_range(start, end)", description: "Integers from start up to end, produced only as they are consumed" },
+    BuiltinInfo { name: "map", signature: "map(seq, f)", description: "f applied to each element; lazy when seq is lazy" },
+    BuiltinInfo { name: "filter", signature: "filter(seq, f)", description: "Elements for which f is truthy; lazy when seq is lazy" },
+    BuiltinInfo { name: "find_index", signature: "find_index(arr, f)", description: "Index of the first element for which f is truthy, or -1" },
+    BuiltinInfo { name: "take", signature: "take(seq, n)", description: "The first n elements of an array or lazy sequence, as an array" },
+    BuiltinInfo { name: "collect", signature: "collect(seq)", description: "Every element of a lazy sequence, as an array" },
+    BuiltinInfo { name: "version", signature: "version()", description: "The interpreter version string" },
+    BuiltinInfo { name: "config", signature: "config(path)", description: "A field of the --args-json input by dotted path, e.g. \"db.port\"; none if missing" },
+    BuiltinInfo { name: "platform", signature: "platform()", description: "The host OS and architecture, e.g. \"linux-x86_64\"" },
+];
+
+impl Interpreter {
+    // Returns None when `name` is not a built-in function
+    pub(crate) fn call_builtin(&mut self, name: &str, args: &[Value]) -> Option<Result<Value, LoaError>> {
+        let result = match name {
+            "len" => builtin_len(args),
+            "push" => builtin_push(args),
+            "display_len" => builtin_display_len(args),
+            "seed" => self.builtin_seed(args),
+            "random" => self.builtin_random(args),
+            "shuffle" => self.builtin_shuffle(args),
+            "div_round" => builtin_div_round(args),
+            "timeit" => self.builtin_timeit(args),
+            "pretty" => builtin_pretty(args),
+            "abs" => builtin_abs(args),
+            "min" => builtin_extremum("min", args),
+            "max" => builtin_extremum("max", args),
+            "input" => self.builtin_input(args),
+            "int" => builtin_int(args),
+            "float" => builtin_float(args),
+            "type" => builtin_type(args),
+            "str" => builtin_str(args),
+            "upper" => map_string("upper", args, str::to_uppercase),
+            "lower" => map_string("lower", args, str::to_lowercase),
+            "trim" => map_string("trim", args, |s| s.trim().to_string()),
+            "format" => builtin_format(args),
+            "range" => builtin_range(args),
+            "split" => builtin_split(args),
+            "join" => builtin_join(args),
+            "contains" => builtin_index_of("contains", args)
+                .map(|index| Value::Bool(!matches!(index, Value::Number(-1)))),
+            "index_of" => builtin_index_of("index_of", args),
+            "keys" => map_entries("keys", args, |(key, _)| Value::String(key.clone())),
+            "values" => map_entries("values", args, |(_, value)| value.clone()),
+            "has" => builtin_has(args),
+            "lazy_range" => builtin_lazy_range(args),
+            "map" => self.builtin_map_filter("map", args),
+            "filter" => self.builtin_map_filter("filter", args),
+            "find_index" => self.builtin_find_index(args),
+            "take" => self.builtin_take(args),
+            "collect" => self.builtin_collect(args),
+            "version" => self.builtin_version(args),
+            "platform" => builtin_platform(args),
+            "config" => self.builtin_config(args),
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    // splitmix64, so a given seed always yields the same sequence
+    fn next_random(&mut self) -> u64 {
+        self.rng_state = self.rng_state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // seed(n): reseed the PRNG so later random()/shuffle() calls are reproducible
+    fn builtin_seed(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args("seed", args, 1)?;
+
+        match &args[0] {
+            Value::Number(n) => {
+                self.set_seed(*n as u64);
+                Ok(Value::None)
+            }
+            other => Err(type_mismatch("seed", "a number", other)),
+        }
+    }
+
+    // random(): a float in [0, 1)
+    fn builtin_random(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args("random", args, 0)?;
+
+        Ok(Value::Float((self.next_random() >> 11) as f64 / (1u64 << 53) as f64))
+    }
+
+    // shuffle(arr): a randomly permuted copy of an array
+    fn builtin_shuffle(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args("shuffle", args, 1)?;
+
+        match &args[0] {
+            Value::Array(items) => {
+                let mut shuffled = items.clone();
+                for i in (1..shuffled.len()).rev() {
+                    let j = (self.next_random() % (i as u64 + 1)) as usize;
+                    shuffled.swap(i, j);
+                }
+                Ok(Value::Array(shuffled))
+            }
+            other => Err(type_mismatch("shuffle", "an array", other)),
+        }
+    }
+
+    // timeit(f, n): call f() n times and return the average seconds per call
+    fn builtin_timeit(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args("timeit", args, 2)?;
+
+        let function = match &args[0] {
+            Value::Function(closure) => closure.clone(),
+            other => return Err(type_mismatch("timeit", "a function", other)),
+        };
+        let runs = match &args[1] {
+            Value::Number(n) if *n > 0 => *n,
+            Value::Number(n) => {
+                return Err(LoaError::new(
+                    LoaErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument("timeit".to_string())),
+                    format!("Function 'timeit' expects a positive run count, found {}", n),
+                    current_file(),
+                    0,
+                    0,
+                ));
+            }
+            other => return Err(type_mismatch("timeit", "a positive integer", other)),
+        };
+
+        let start = Instant::now();
+        for _ in 0..runs {
+            self.call_closure(&function, Vec::new())?;
+            if self.is_interrupted() {
+                break;
+            }
+        }
+
+        Ok(Value::Float(start.elapsed().as_secs_f64() / runs as f64))
+    }
+
+    // map(seq, f)/filter(seq, f): eager over arrays, another stage on a lazy sequence
+    fn builtin_map_filter(&mut self, name: &str, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args(name, args, 2)?;
+
+        let function = match &args[1] {
+            Value::Function(function) => function.clone(),
+            other => return Err(type_mismatch(name, "a function", other)),
+        };
+
+        match &args[0] {
+            Value::Lazy(seq) => {
+                let stage = if name == "map" { LazyStage::Map(function) } else { LazyStage::Filter(function) };
+                Ok(Value::Lazy(seq.with_stage(stage)))
+            }
+            Value::Array(items) => {
+                let mut result = Vec::new();
+                for item in items {
+                    let output = self.call_closure(&function, vec![item.clone()])?;
+                    if name == "map" {
+                        result.push(output);
+                    } else if is_truthy(&output) {
+                        result.push(item.clone());
+                    }
+                }
+                Ok(Value::Array(result))
+            }
+            other => Err(type_mismatch(name, "an array or a lazy sequence", other)),
+        }
+    }
+
+    // find_index(arr, f): stops calling f at the first match
+    fn builtin_find_index(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args("find_index", args, 2)?;
+
+        let function = match &args[1] {
+            Value::Function(function) => function.clone(),
+            other => return Err(type_mismatch("find_index", "a function", other)),
+        };
+        let items = match &args[0] {
+            Value::Array(items) => items,
+            other => return Err(type_mismatch("find_index", "an array", other)),
+        };
+
+        for (index, item) in items.iter().enumerate() {
+            if is_truthy(&self.call_closure(&function, vec![item.clone()])?) {
+                return Ok(Value::Number(index as i64));
+            }
+        }
+        Ok(Value::Number(-1))
+    }
+
+    // take(seq, n): only the first n elements are ever computed
+    fn builtin_take(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args("take", args, 2)?;
+
+        let count = match &args[1] {
+            Value::Number(n) => (*n).max(0) as usize,
+            other => return Err(type_mismatch("take", "a count", other)),
+        };
+
+        match &args[0] {
+            Value::Lazy(seq) => Ok(Value::Array(self.collect_lazy(seq, Some(count))?)),
+            Value::Array(items) => Ok(Value::Array(items.iter().take(count).cloned().collect())),
+            other => Err(type_mismatch("take", "an array or a lazy sequence", other)),
+        }
+    }
+
+    // collect(seq): materialize a lazy sequence
+    fn builtin_collect(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args("collect", args, 1)?;
+
+        match &args[0] {
+            Value::Lazy(seq) => Ok(Value::Array(self.collect_lazy(seq, None)?)),
+            Value::Array(items) => Ok(Value::Array(items.clone())),
+            other => Err(type_mismatch("collect", "a lazy sequence", other)),
+        }
+    }
+
+    // input(prompt?): one line from stdin (or the reader given to
+    // `set_input`) without its line ending; None at EOF
+    fn builtin_input(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        match args {
+            [] => {}
+            [prompt] => {
+                write!(self.output, "{}", prompt).ok();
+                self.output.flush().ok();
+            }
+            _ => expect_args("input", args, 1)?,
+        }
+
+        let mut line = String::new();
+        let read = match self.input.as_mut() {
+            Some(input) => input.read_line(&mut line),
+            None => io::stdin().lock().read_line(&mut line),
+        };
+        match read {
+            Ok(0) | Err(_) => Ok(Value::None),
+            Ok(_) => Ok(Value::String(line.trim_end_matches(['\n', '\r']).to_string())),
+        }
+    }
+
+    // version(): the interpreter version, as printed by `loa --version`
+    fn builtin_version(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        expect_args("version", args, 0)?;
+
+        Ok(Value::String(self.version.clone()))
+    }
+
+    // config(path): the `--args-json` value at a dotted path, or the whole
+    // document without one; missing fields are none
+    fn builtin_config(&mut self, args: &[Value]) -> Result<Value, LoaError> {
+        let path = match args {
+            [] => "",
+            [Value::String(path)] => path.as_str(),
+            [other] => return Err(type_mismatch("config", "a string path", other)),
+            _ => return expect_args("config", args, 1).map(|_| Value::None),
+        };
+
+        let Some(json) = self.config.as_ref().and_then(|config| config.get(path)) else {
+            return Ok(Value::None);
+        };
+        Ok(json.to_value())
+    }
+}
+
+fn expect_args(name: &str, args: &[Value], count: usize) -> Result<(), LoaError> {
+    if args.len() != count {
+        return Err(LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::ArityMismatch(name.to_string())),
+            format!("Function '{}' expects {} argument(s), found {}", name, count, args.len()),
+            current_file(),
+            0,
+            0,
+        ));
+    }
+    Ok(())
+}
+
+fn type_mismatch(name: &str, expected: &str, found: &Value) -> LoaError {
+    LoaError::new(
+        LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError(name.to_string())),
+        format!("Function '{}' expects {}, found {}", name, expected, found.type_name()),
+        current_file(),
+        0,
+        0,
+    )
+}
+
+// len(x): Unicode scalar values in a string, or elements in an array
+fn builtin_len(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("len", args, 1)?;
+
+    match &args[0] {
+        Value::String(s) => Ok(Value::Number(s.chars().count() as i64)),
+        Value::Array(items) => Ok(Value::Number(items.len() as i64)),
+        Value::Map(map) => Ok(Value::Number(map.len() as i64)),
+        other => Err(type_mismatch("len", "a string, an array or a map", other)),
+    }
+}
+
+// push(arr, x): arrays are values, so the argument itself is left unchanged
+fn builtin_push(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("push", args, 2)?;
+
+    match &args[0] {
+        Value::Array(items) => {
+            let mut items = items.clone();
+            items.push(args[1].clone());
+            Ok(Value::Array(items))
+        }
+        other => Err(type_mismatch("push", "an array", other)),
+    }
+}
+
+// display_len(s): number of grapheme clusters in a string
+fn builtin_display_len(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("display_len", args, 1)?;
+
+    match &args[0] {
+        Value::String(s) => Ok(Value::Number(count_graphemes(s) as i64)),
+        other => Err(type_mismatch("display_len", "a string", other)),
+    }
+}
+
+// range(start, end, step?): end is excluded, as with lazy_range
+fn builtin_range(args: &[Value]) -> Result<Value, LoaError> {
+    let step = match args {
+        [_, _] => 1,
+        [_, _, Value::Number(0)] => {
+            return Err(LoaError::new(
+                LoaErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument("range".to_string())),
+                "Function 'range' expects a non-zero step".to_string(),
+                current_file(),
+                0,
+                0,
+            ));
+        }
+        [_, _, Value::Number(step)] => *step,
+        [_, _, other] => return Err(type_mismatch("range", "an integer step", other)),
+        _ => {
+            // Reports the arity error; two or three arguments never get here
+            expect_args("range", args, 3)?;
+            1
+        }
+    };
+
+    let (start, end) = match (&args[0], &args[1]) {
+        (Value::Number(start), Value::Number(end)) => (*start, *end),
+        (Value::Number(_), other) | (other, _) => return Err(type_mismatch("range", "integer bounds", other)),
+    };
+
+    let mut items = Vec::new();
+    let mut n = start;
+    while (step > 0 && n < end) || (step < 0 && n > end) {
+        items.push(Value::Number(n));
+        n = match n.checked_add(step) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(Value::Array(items))
+}
+
+// split(s, delim): like Python's str.split with a separator, except that an
+// empty delimiter splits into characters instead of failing
+fn builtin_split(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("split", args, 2)?;
+
+    let pieces: Vec<Value> = match (&args[0], &args[1]) {
+        (Value::String(s), Value::String(delim)) if delim.is_empty() => {
+            s.chars().map(|c| Value::String(c.to_string())).collect()
+        }
+        (Value::String(s), Value::String(delim)) => {
+            s.split(delim.as_str()).map(|piece| Value::String(piece.to_string())).collect()
+        }
+        (Value::String(_), other) | (other, _) => return Err(type_mismatch("split", "two strings", other)),
+    };
+    Ok(Value::Array(pieces))
+}
+
+// join(arr, sep): the inverse of split for an array of strings
+fn builtin_join(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("join", args, 2)?;
+
+    match (&args[0], &args[1]) {
+        (Value::Array(items), Value::String(sep)) => {
+            let parts: Vec<String> = items.iter().map(Value::to_string).collect();
+            Ok(Value::String(parts.join(sep)))
+        }
+        (Value::Array(_), other) => Err(type_mismatch("join", "a string separator", other)),
+        (other, _) => Err(type_mismatch("join", "an array", other)),
+    }
+}
+
+// index_of(x, item): string positions count characters, as len() does
+fn builtin_index_of(name: &str, args: &[Value]) -> Result<Value, LoaError> {
+    expect_args(name, args, 2)?;
+
+    let index = match (&args[0], &args[1]) {
+        (Value::String(s), Value::String(needle)) => s.find(needle.as_str()).map(|byte| s[..byte].chars().count()),
+        (Value::String(_), other) => return Err(type_mismatch(name, "a string to search for", other)),
+        (Value::Array(items), item) => items.iter().position(|element| element == item),
+        (other, _) => return Err(type_mismatch(name, "a string or an array", other)),
+    };
+    Ok(Value::Number(index.map_or(-1, |index| index as i64)))
+}
+
+// keys(m) and values(m): one element per entry, in insertion order
+fn map_entries(name: &str, args: &[Value], entry: fn(&(String, Value)) -> Value) -> Result<Value, LoaError> {
+    expect_args(name, args, 1)?;
+
+    match &args[0] {
+        Value::Map(map) => Ok(Value::Array(map.iter().map(entry).collect())),
+        other => Err(type_mismatch(name, "a map", other)),
+    }
+}
+
+fn builtin_has(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("has", args, 2)?;
+
+    match (&args[0], &args[1]) {
+        (Value::Map(map), Value::String(key)) => Ok(Value::Bool(map_get(map, key).is_some())),
+        (Value::Map(_), other) => Err(type_mismatch("has", "a string key", other)),
+        (other, _) => Err(type_mismatch("has", "a map", other)),
+    }
+}
+
+// lazy_range(start, end): nothing is allocated until the range is consumed
+fn builtin_lazy_range(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("lazy_range", args, 2)?;
+
+    match (&args[0], &args[1]) {
+        (Value::Number(start), Value::Number(end)) => Ok(Value::Lazy(LazySeq::range(*start, *end))),
+        (Value::Number(_), other) | (other, _) => Err(type_mismatch("lazy_range", "integer bounds", other)),
+    }
+}
+
+
+fn parse_error(name: &str, text: &str, expected: &str) -> LoaError {
+    LoaError::new(
+        LoaErrorKind::RuntimeError(RuntimeErrorKind::ParseError(name.to_string())),
+        format!("Function '{}' cannot parse {:?} as {}", name, text, expected),
+        current_file(),
+        0,
+        0,
+    )
+}
+
+// int(x): strings are parsed, floats truncate toward zero
+fn builtin_int(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("int", args, 1)?;
+
+    match &args[0] {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::Float(x) if x.is_finite() => Ok(Value::Number(x.trunc() as i64)),
+        Value::Float(x) => Err(parse_error("int", &x.to_string(), "an integer")),
+        Value::String(s) => s.trim().parse().map(Value::Number).map_err(|_| parse_error("int", s, "an integer")),
+        other => Err(type_mismatch("int", "a string or a number", other)),
+    }
+}
+
+// float(x): strings are parsed, integers widen
+fn builtin_float(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("float", args, 1)?;
+
+    match &args[0] {
+        Value::Number(n) => Ok(Value::Float(*n as f64)),
+        Value::Float(x) => Ok(Value::Float(*x)),
+        Value::String(s) => s.trim().parse().map(Value::Float).map_err(|_| parse_error("float", s, "a float")),
+        other => Err(type_mismatch("float", "a string or a number", other)),
+    }
+}
+
+// str(x): the same text print would show for x
+fn builtin_str(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("str", args, 1)?;
+
+    Ok(Value::String(args[0].to_string()))
+}
+
+// format(template, x, ...): exactly one value per `{}`
+fn builtin_format(args: &[Value]) -> Result<Value, LoaError> {
+    let template = match args.first() {
+        Some(Value::String(template)) => template,
+        Some(other) => return Err(type_mismatch("format", "a string template", other)),
+        None => {
+            return Err(LoaError::new(
+                LoaErrorKind::RuntimeError(RuntimeErrorKind::ArityMismatch("format".to_string())),
+                "Function 'format' expects at least 1 argument, found 0".to_string(),
+                current_file(),
+                0,
+                0,
+            ))
+        }
+    };
+
+    let parts = parse_format_string(template);
+    let placeholders = parts.iter().filter(|part| matches!(part, FormatPart::Placeholder)).count();
+    let mut values = args[1..].iter();
+    if values.len() != placeholders {
+        return Err(LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::ArityMismatch("format".to_string())),
+            format!("Function 'format' has {} placeholder(s) but was given {} value(s)", placeholders, values.len()),
+            current_file(),
+            0,
+            0,
+        ));
+    }
+
+    let mut out = String::new();
+    for part in parts {
+        match part {
+            FormatPart::Literal(text) => out.push_str(&text),
+            FormatPart::Placeholder => out.push_str(&values.next().map(Value::to_string).unwrap_or_default()),
+        }
+    }
+    Ok(Value::String(out))
+}
+
+// type(x): "number", "float", "string", "bool", "array", "function", "lazy" or "none"
+fn builtin_type(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("type", args, 1)?;
+
+    Ok(Value::String(args[0].type_name().to_string()))
+}
+
+// upper(s)/lower(s)/trim(s): a new string derived from a single string argument
+fn map_string(name: &str, args: &[Value], f: impl Fn(&str) -> String) -> Result<Value, LoaError> {
+    expect_args(name, args, 1)?;
+
+    match &args[0] {
+        Value::String(s) => Ok(Value::String(f(s))),
+        other => Err(type_mismatch(name, "a string", other)),
+    }
+}
+
+// abs(x): absolute value, keeping the int/float kind
+fn builtin_abs(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("abs", args, 1)?;
+
+    match &args[0] {
+        Value::Number(n) => n.checked_abs().map(Value::Number).ok_or_else(|| LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::IntegerOverflow("abs".to_string())),
+            "Integer overflow in 'abs'; the result does not fit in 64 bits",
+            current_file(),
+            0,
+            0,
+        )),
+        Value::Float(x) => Ok(Value::Float(x.abs())),
+        other => Err(type_mismatch("abs", "a number", other)),
+    }
+}
+
+// min(...)/max(...): the extremum of one or more numbers. Integers are
+// compared exactly; any float among the arguments makes the result a float.
+fn builtin_extremum(name: &str, args: &[Value]) -> Result<Value, LoaError> {
+    if args.is_empty() {
+        return Err(LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::ArityMismatch(name.to_string())),
+            format!("Function '{}' expects at least 1 argument, found 0", name),
+            current_file(),
+            0,
+            0,
+        ));
+    }
+
+    let mut best: Option<&Value> = None;
+    let mut any_float = false;
+    for arg in args {
+        match arg {
+            Value::Number(_) => {}
+            Value::Float(_) => any_float = true,
+            other => return Err(type_mismatch(name, "numbers", other)),
+        }
+
+        let replaces = match best {
+            None => true,
+            Some(current) if name == "min" => compare_numbers(arg, current).is_lt(),
+            Some(current) => compare_numbers(arg, current).is_gt(),
+        };
+        if replaces {
+            best = Some(arg);
+        }
+    }
+
+    Ok(match best {
+        Some(Value::Number(n)) if any_float => Value::Float(*n as f64),
+        Some(value) => value.clone(),
+        None => Value::None,
+    })
+}
+
+fn compare_numbers(a: &Value, b: &Value) -> std::cmp::Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.cmp(b),
+        _ => as_f64(a).total_cmp(&as_f64(b)),
+    }
+}
+
+fn as_f64(value: &Value) -> f64 {
+    match value {
+        Value::Number(n) => *n as f64,
+        Value::Float(x) => *x,
+        _ => f64::NAN,
+    }
+}
+
+// div_round(a, b, mode): integer division with an explicit rounding mode
+fn builtin_div_round(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("div_round", args, 3)?;
+
+    let (a, b) = match (&args[0], &args[1]) {
+        (Value::Number(a), Value::Number(b)) => (*a, *b),
+        (Value::Number(_), other) | (other, _) => return Err(type_mismatch("div_round", "integer operands", other)),
+    };
+    let mode = match &args[2] {
+        Value::String(mode) => mode.as_str(),
+        other => return Err(type_mismatch("div_round", "a rounding mode string", other)),
+    };
+
+    if b == 0 {
+        return Err(LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::DivideByZero),
+            "Division by zero in 'div_round'",
+            current_file(),
+            0,
+            0,
+        ));
+    }
+
+    let quotient = a.wrapping_div(b);
+    let remainder = a.wrapping_rem(b);
+    // Direction of the exact result, used to step the truncated quotient
+    let step = if (a < 0) == (b < 0) { 1 } else { -1 };
+
+    let result = match mode {
+        "trunc" => quotient,
+        "floor" if remainder != 0 && step < 0 => quotient - 1,
+        "floor" => quotient,
+        "ceil" if remainder != 0 && step > 0 => quotient + 1,
+        "ceil" => quotient,
+        "half_up" if remainder.unsigned_abs() as u128 * 2 >= b.unsigned_abs() as u128 => quotient + step,
+        "half_up" => quotient,
+        _ => {
+            return Err(LoaError::new(
+                LoaErrorKind::RuntimeError(RuntimeErrorKind::InvalidArgument("div_round".to_string())),
+                format!("Unknown rounding mode '{}', expected \"floor\", \"ceil\", \"half_up\" or \"trunc\"", mode),
+                current_file(),
+                0,
+                0,
+            ));
+        }
+    };
+
+    Ok(Value::Number(result))
+}
+
+// platform(): "<os>-<arch>" of the host running the script
+fn builtin_platform(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("platform", args, 0)?;
+
+    Ok(Value::String(format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)))
+}
+
+// pretty(x): nested arrays and maps laid out one element per line, like a JSON pretty-printer
+fn builtin_pretty(args: &[Value]) -> Result<Value, LoaError> {
+    expect_args("pretty", args, 1)?;
+
+    let mut out = String::new();
+    write_pretty(&args[0], 0, &mut out);
+    Ok(Value::String(out))
+}
+
+fn write_pretty(value: &Value, indent: usize, out: &mut String) {
+    match value {
+        Value::Array(_) | Value::Map(_) if indent >= MAX_DISPLAY_DEPTH => out.push_str("[...]"),
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (index, item) in items.iter().enumerate() {
+                out.push_str(&"  ".repeat(indent + 1));
+                write_pretty(item, indent + 1, out);
+                if index + 1 < items.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push(']');
+        }
+        Value::Map(entries) if !entries.is_empty() => {
+            out.push_str("{\n");
+            for (index, (key, item)) in entries.iter().enumerate() {
+                out.push_str(&"  ".repeat(indent + 1));
+                out.push_str(&format!("{:?}: ", key));
+                write_pretty(item, indent + 1, out);
+                if index + 1 < entries.len() {
+                    out.push(',');
+                }
+                out.push('\n');
+            }
+            out.push_str(&"  ".repeat(indent));
+            out.push('}');
+        }
+        Value::String(s) if indent > 0 => out.push_str(&format!("{:?}", s)),
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+// A minimal grapheme segmentation: combining marks, variation selectors,
+// emoji modifiers and tags extend the previous cluster, ZWJ joins the next
+// character, regional indicators pair up into flags and CR LF stays together.
+fn count_graphemes(s: &str) -> usize {
+    let mut count = 0;
+    let mut prev: Option<char> = None;
+    let mut regional_run = 0;
+
+    for c in s.chars() {
+        let starts_cluster = match prev {
+            None => true,
+            Some('\r') if c == '\n' => false,
+            Some('\u{200D}') => false,
+            Some(p) if is_regional_indicator(p) && is_regional_indicator(c) => regional_run % 2 == 0,
+            _ => !is_extending(c),
+        };
+
+        regional_run = if is_regional_indicator(c) { regional_run + 1 } else { 0 };
+
+        if starts_cluster {
+            count += 1;
+        }
+        prev = Some(c);
+    }
+
+    count
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+fn is_extending(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{200D}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}'
+        | '\u{E0100}'..='\u{E01EF}'
+    )
+}
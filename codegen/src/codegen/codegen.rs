@@ -1,9 +1,59 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use lexer::Lexer;
 use parser::ast::*;
-use ::error::{LoaError, LoaErrorKind};
+use parser::parse;
+use crate::{Json, LazySeq};
+use ::error::{current_file, current_source, set_current_file, set_current_source, LoaError, LoaErrorKind, RuntimeErrorKind};
 
 pub struct Interpreter {
     pub variables: HashMap<String, Value>,
+    pub functions: HashMap<String, Rc<Closure>>,
+    // Names declared with `const` at the top level
+    constants: HashSet<String>,
+    frames: Vec<Frame>,
+    imported_modules: HashSet<PathBuf>,
+    // Files currently being executed, outermost first, to detect import cycles
+    import_stack: Vec<PathBuf>,
+    pub(crate) rng_state: u64,
+    pub(crate) version: String,
+    // Structured input from `loa run --args-json`, read with config()
+    pub(crate) config: Option<Json>,
+    interrupted: Arc<AtomicBool>,
+    // Integer overflow wraps around instead of raising IntegerOverflow
+    wrapping_arithmetic: bool,
+    // Where print and input prompts go; stdout unless set with `with_output`
+    pub(crate) output: Box<dyn Write>,
+    // Where input() reads lines from; stdin unless set with `set_input`
+    pub(crate) input: Option<Box<dyn BufRead>>,
+    // While `run` is active, the first runtime error is kept here instead of printed
+    capture_errors: bool,
+    captured_error: Option<LoaError>,
+    // Nested function calls in progress, checked against `recursion_limit`
+    call_depth: usize,
+    recursion_limit: usize,
+    // Line and column of the call or operator being evaluated, given to
+    // runtime errors created without a position; 0:0 between expressions
+    position: (usize, usize),
+}
+
+// Deep enough for ordinary recursive code, shallow enough that the native
+// stack of the `loa` binary does not overflow first
+pub const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+// A function value. One defined inside another function keeps a copy of
+// the enclosing function's locals as they were at its definition
+#[derive(Debug)]
+pub struct Closure {
+    pub function: FunctionNode,
+    pub captured: HashMap<String, Value>,
 }
 
 #[derive(Debug, Clone)]
@@ -12,101 +62,799 @@ pub enum Value {
     Float(f64),
     String(String),
     Bool(bool),
+    Array(Vec<Value>),
+    // String keys in insertion order, like a JSON object
+    Map(Vec<(String, Value)>),
+    Function(Rc<Closure>),
+    Lazy(LazySeq),
     None,
 }
 
+impl From<&parser::ast::Value> for Value {
+    fn from(value: &parser::ast::Value) -> Self {
+        match value {
+            parser::ast::Value::Int(n) => Value::Number(*n),
+            parser::ast::Value::Float(f) => Value::Float(*f),
+            parser::ast::Value::Text(s) => Value::String(s.clone()),
+            parser::ast::Value::Bool(b) => Value::Bool(*b),
+        }
+    }
+}
+
+// Arrays and maps nested deeper than this print as `[...]`, both by print
+// and by pretty(). Values are copied, so a true cycle cannot form, but a
+// value built by repeatedly wrapping itself (`a = [a]` in a loop) can still
+// nest deep enough to exhaust the stack while printing.
+pub(crate) const MAX_DISPLAY_DEPTH: usize = 64;
+
+impl Value {
+    // As returned by type() and named in type errors
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bool(_) => "bool",
+            Value::Array(_) => "array",
+            Value::Map(_) => "map",
+            Value::Function(_) => "function",
+            Value::Lazy(_) => "lazy",
+            Value::None => "none",
+        }
+    }
+
+    fn write_nested(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        match self {
+            Value::Array(_) | Value::Map(_) if depth >= MAX_DISPLAY_DEPTH => write!(f, "[...]"),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    // Quote nested strings so ["1"] and [1] stay distinguishable
+                    match item {
+                        Value::String(s) => write!(f, "{:?}", s)?,
+                        other => other.write_nested(f, depth + 1)?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}: ", key)?;
+                    match value {
+                        Value::String(s) => write!(f, "{:?}", s)?,
+                        other => other.write_nested(f, depth + 1)?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            // Keep a decimal point on whole floats so 5.0 never reads as the integer 5
+            Value::Float(x) if x.is_finite() && x.fract() == 0.0 => write!(f, "{:.1}", x),
+            // Rust would print NaN; keep all three special values lower case
+            Value::Float(x) if x.is_nan() => write!(f, "nan"),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Array(_) | Value::Map(_) => self.write_nested(f, 0),
+            Value::Function(closure) => write!(f, "<function {}>", closure.function.name),
+            Value::Lazy(seq) => write!(f, "{}", seq),
+            Value::None => write!(f, "none"),
+        }
+    }
+}
+
+// Integers and floats compare by numeric value, so 1 == 1.0, and NaN equals
+// nothing, not even itself (`<` and the other orderings are false for it too).
+// Arrays compare element by element, maps by their entries in any order;
+// none equals only none. Values of
+// different types are never equal, nor are two lazy sequences, which have no
+// elements until consumed
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Number(a), Value::Float(b)) | (Value::Float(b), Value::Number(a)) => *a as f64 == *b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => {
+                a.len() == b.len() && a.iter().all(|(key, value)| map_get(b, key) == Some(value))
+            }
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::None, Value::None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A function call's local variables
+#[derive(Default)]
+struct Frame {
+    variables: HashMap<String, Value>,
+    constants: HashSet<String>,
+}
+
+// How a block finished executing
+enum Flow {
+    Normal,
+    Return(Value),
+    Break,
+    Continue,
+    Interrupted,
+}
+
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+
+    // Sends program output to `output` instead of stdout, e.g. to capture it
+    pub fn with_output(output: impl Write + 'static) -> Self {
         Interpreter {
             variables: HashMap::new(),
+            functions: HashMap::new(),
+            constants: HashSet::new(),
+            frames: Vec::new(),
+            imported_modules: HashSet::new(),
+            import_stack: Vec::new(),
+            rng_state: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            config: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            wrapping_arithmetic: false,
+            output: Box::new(output),
+            input: None,
+            capture_errors: false,
+            captured_error: None,
+            call_depth: 0,
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            position: (0, 0),
         }
     }
 
+    // Makes input() read from `input` instead of stdin, e.g. to script it
+    pub fn set_input(&mut self, input: impl BufRead + 'static) {
+        self.input = Some(Box::new(input));
+    }
+
+    // The version reported by version(); the CLI passes its own
+    pub fn set_version(&mut self, version: &str) {
+        self.version = version.to_string();
+    }
+
+    pub fn set_config(&mut self, config: Json) {
+        self.config = Some(config);
+    }
+
+    // Setting the returned flag (e.g. from a Ctrl-C handler) stops the
+    // running program at the next statement or loop iteration
+    pub fn interrupt_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    pub(crate) fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+
+    // Reports an interrupt that stopped the last top-level run and clears it
+    fn finish_interrupt(&mut self) {
+        if self.interrupted.swap(false, Ordering::SeqCst) {
+            self.captured_error.take().unwrap_or_else(interrupted_error).display();
+        }
+    }
+
+    // Prints a runtime error and carries on, or under `run` records the
+    // first one and stops the program as an interrupt would
+    pub(crate) fn raise(&mut self, error: LoaError) {
+        let error = match error.line {
+            0 => LoaError { line: self.position.0, column: self.position.1, ..error },
+            _ => error,
+        };
+        if !self.capture_errors {
+            error.display();
+            return;
+        }
+        if self.captured_error.is_none() {
+            self.captured_error = Some(error);
+        }
+        self.interrupted.store(true, Ordering::SeqCst);
+    }
+
+    // Stops the program at an error there is no sensible way to continue
+    // past, reported once the top-level run unwinds
+    fn abort(&mut self, error: LoaError) {
+        if self.captured_error.is_none() {
+            self.captured_error = Some(LoaError { line: self.position.0, column: self.position.1, ..error });
+        }
+        self.interrupted.store(true, Ordering::SeqCst);
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng_state = seed;
+    }
+
+    // Opts into two's-complement wrapping, e.g. for hashing code that relies on it
+    pub fn set_wrapping_arithmetic(&mut self, wrapping: bool) {
+        self.wrapping_arithmetic = wrapping;
+    }
+
+    // Each call uses native stack, so a higher limit may need a thread
+    // with a larger stack than the default
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+        self.recursion_limit = limit;
+    }
+
+    // Evaluates one expression against the current state, e.g. for a REPL
+    // to echo; like `run`, the first runtime error is returned, not printed
+    pub fn evaluate(&mut self, expr: &Expression) -> Result<Value, LoaError> {
+        self.interrupted.store(false, Ordering::SeqCst);
+        self.position = (0, 0);
+        self.capture_errors = true;
+        let value = self.evaluate_expression(expr);
+        self.capture_errors = false;
+        self.finish_capture().map(|()| value)
+    }
+
     pub fn execute(&mut self, ast: &[ASTNode]) {
+        self.interrupted.store(false, Ordering::SeqCst);
+        self.execute_block(ast);
+        self.finish_interrupt();
+    }
+
+    // Like `execute`, but stops at the first runtime error and returns it
+    // instead of printing it, for embedding Loa in other programs
+    pub fn run(&mut self, ast: &[ASTNode]) -> Result<(), LoaError> {
+        self.interrupted.store(false, Ordering::SeqCst);
+        self.capture_errors = true;
+        self.execute_block(ast);
+        self.capture_errors = false;
+        self.finish_capture()
+    }
+
+    // The error `run` or `evaluate` stopped at, if any, clearing it
+    fn finish_capture(&mut self) -> Result<(), LoaError> {
+        let interrupted = self.interrupted.swap(false, Ordering::SeqCst);
+        match self.captured_error.take() {
+            Some(error) => Err(error),
+            None if interrupted => Err(interrupted_error()),
+            None => Ok(()),
+        }
+    }
+
+    fn execute_block(&mut self, ast: &[ASTNode]) -> Flow {
         for node in ast {
-            self.execute_node(node);
+            if self.is_interrupted() {
+                return Flow::Interrupted;
+            }
+            match self.execute_node(node) {
+                Flow::Normal => {}
+                flow => return flow,
+            }
         }
+        Flow::Normal
     }
 
-    fn execute_node(&mut self, node: &ASTNode) {
+    fn execute_node(&mut self, node: &ASTNode) -> Flow {
+        self.position = (0, 0);
         match node {
             ASTNode::Statement(stmt) => self.execute_statement(stmt),
-            _ => {}
+            ASTNode::Function(function) => {
+                // Top-level functions read globals when called, so only
+                // nested ones need to capture anything
+                let Some(frame) = self.frames.last() else {
+                    let closure = Closure { function: function.clone(), captured: HashMap::new() };
+                    self.functions.insert(function.name.clone(), Rc::new(closure));
+                    return Flow::Normal;
+                };
+                let closure = Closure { function: function.clone(), captured: frame.variables.clone() };
+                if let Err(error) = self.declare_variable(&function.name, Value::Function(Rc::new(closure)), false) {
+                    self.raise(error);
+                }
+                Flow::Normal
+            }
+            ASTNode::Variable(variable) => {
+                let value = match &variable.initial_value {
+                    Some(value) => self.evaluate_expression(value),
+                    None => Value::None,
+                };
+                let constant = matches!(variable.mutability, Mutability::Const);
+                if let Err(error) = self.declare_variable(&variable.name, value, constant) {
+                    self.raise(error);
+                }
+                Flow::Normal
+            }
+            _ => Flow::Normal,
+        }
+    }
+
+    fn get_variable(&self, name: &str) -> Value {
+        if let Some(value) = self.frames.last().and_then(|frame| frame.variables.get(name)) {
+            return value.clone();
+        }
+        if let Some(value) = self.variables.get(name) {
+            return value.clone();
+        }
+        // A bare function name refers to the function itself, e.g. timeit(work, 10)
+        if let Some(closure) = self.functions.get(name) {
+            return Value::Function(Rc::clone(closure));
+        }
+        Value::None
+    }
+
+    // Assignment updates the innermost existing binding, so a function can
+    // change a global; a name bound nowhere yet is created in the current scope
+    fn set_variable(&mut self, name: &str, value: Value) -> Result<(), LoaError> {
+        if let Some(frame) = self.frames.last_mut().filter(|frame| frame.variables.contains_key(name)) {
+            if frame.constants.contains(name) {
+                return Err(assign_to_const(name));
+            }
+            frame.variables.insert(name.to_string(), value);
+        } else if self.variables.contains_key(name) {
+            if self.constants.contains(name) {
+                return Err(assign_to_const(name));
+            }
+            self.variables.insert(name.to_string(), value);
+        } else {
+            self.declare_variable(name, value, false)?;
+        }
+        Ok(())
+    }
+
+    // `var`/`let`/`const` always bind in the current scope, shadowing any
+    // global; only a const in that same scope can't be redeclared
+    fn declare_variable(&mut self, name: &str, value: Value, constant: bool) -> Result<(), LoaError> {
+        let (variables, constants) = match self.frames.last_mut() {
+            Some(frame) => (&mut frame.variables, &mut frame.constants),
+            None => (&mut self.variables, &mut self.constants),
+        };
+
+        if constants.contains(name) {
+            return Err(assign_to_const(name));
+        }
+        if constant {
+            constants.insert(name.to_string());
         }
+        variables.insert(name.to_string(), value);
+        Ok(())
     }
 
-    fn execute_statement(&mut self, stmt: &StatementNode) {
+    fn import_module(&mut self, path: &str, once: bool) -> Result<(), LoaError> {
+        let import_error = |message: String| LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::ImportError(path.to_string())),
+            message,
+            current_file(),
+            0,
+            0,
+        );
+
+        // Relative paths resolve against the importing file's directory
+        let importer = current_file();
+        let importer_source = current_source();
+        let importer_path = Path::new(&importer);
+        let module_path = match importer_path.parent() {
+            Some(dir) if importer_path.is_file() => dir.join(path),
+            _ => PathBuf::from(path),
+        };
+
+        let resolved = fs::canonicalize(&module_path)
+            .map_err(|err| import_error(format!("Cannot import '{}': {}", path, err)))?;
+
+        // The top-level script is the root of the import chain
+        let is_outermost = self.import_stack.is_empty();
+        if is_outermost {
+            if let Ok(root) = fs::canonicalize(importer_path) {
+                self.import_stack.push(root);
+            }
+        }
+
+        let result = self.run_module(path, &module_path, resolved, once);
+
+        if is_outermost {
+            self.import_stack.clear();
+        }
+        set_current_file(importer);
+        set_current_source(importer_source);
+
+        result
+    }
+
+    fn run_module(&mut self, path: &str, module_path: &Path, resolved: PathBuf, once: bool) -> Result<(), LoaError> {
+        let import_error = |message: String| LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::ImportError(path.to_string())),
+            message,
+            current_file(),
+            0,
+            0,
+        );
+
+        if once && self.imported_modules.contains(&resolved) {
+            return Ok(());
+        }
+
+        if self.import_stack.contains(&resolved) {
+            let chain: Vec<String> = self.import_stack.iter()
+                .chain(std::iter::once(&resolved))
+                .map(|module| module.file_name().unwrap_or_default().to_string_lossy().into_owned())
+                .collect();
+            return Err(import_error(format!("Import cycle: {}", chain.join(" -> "))));
+        }
+
+        if self.imported_modules.contains(&resolved) {
+            return Err(import_error(format!("'{}' is already imported, use 'import once' to allow repeats", path)));
+        }
+
+        let code = fs::read_to_string(&resolved)
+            .map_err(|err| import_error(format!("Cannot read '{}': {}", path, err)))?;
+        // Diagnostics raised inside the module point at the module itself
+        set_current_file(module_path.to_string_lossy());
+        set_current_source(code.as_str());
+
+        let tokens = Lexer::new(&code).tokenize();
+        let ast = match parse(&tokens) {
+            Ok(ast) => ast,
+            Err(errors) => {
+                for error in errors {
+                    self.raise(error);
+                }
+                return Err(import_error(format!("Failed to parse module '{}'", path)));
+            }
+        };
+
+        self.imported_modules.insert(resolved.clone());
+        self.import_stack.push(resolved);
+
+        // Module definitions always land in the global scope
+        let frames = std::mem::take(&mut self.frames);
+        self.execute_block(&ast);
+        self.frames = frames;
+
+        self.import_stack.pop();
+
+        Ok(())
+    }
+
+    // `position` locates the call, once the arguments have been evaluated
+    fn call_function(&mut self, name: &str, args: &[Expression], position: (usize, usize)) -> Result<Value, LoaError> {
+        let values: Vec<Value> = args.iter().map(|arg| self.evaluate_expression(arg)).collect();
+        self.position = position;
+        self.call_with_values(name, values)
+    }
+
+    // The receiver becomes the first argument, so `s.upper()` is `upper(s)`
+    fn call_method(&mut self, receiver: &Expression, method: &str, args: &[Expression], position: (usize, usize)) -> Result<Value, LoaError> {
+        let mut values = vec![self.evaluate_expression(receiver)];
+        values.extend(args.iter().map(|arg| self.evaluate_expression(arg)));
+        self.position = position;
+        self.call_with_values(method, values)
+    }
+
+    // A local function (a nested definition) shadows a global one, which
+    // shadows a built-in of the same name
+    pub(crate) fn call_with_values(&mut self, name: &str, args: Vec<Value>) -> Result<Value, LoaError> {
+        let value = match self.get_variable(name) {
+            Value::Function(closure) => return self.call_closure(&closure, args),
+            value => value,
+        };
+        if let Some(result) = self.call_builtin(name, &args) {
+            return result;
+        }
+
+        // A misspelled name used as a statement would otherwise do nothing at all
+        if let Value::None = value {
+            return Err(LoaError::new(
+                LoaErrorKind::RuntimeError(RuntimeErrorKind::UndefinedVariable(name.to_string())),
+                format!("No function or built-in named '{}'", name),
+                current_file(),
+                0,
+                0,
+            ));
+        }
+        Err(LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError(name.to_string())),
+            format!("'{}' is a {}, not a function", name, value.type_name()),
+            current_file(),
+            0,
+            0,
+        ))
+    }
+
+    pub(crate) fn call_closure(&mut self, closure: &Rc<Closure>, args: Vec<Value>) -> Result<Value, LoaError> {
+        let function = &closure.function;
+        let name = &function.name;
+
+        let required = function.parameters.iter()
+            .rposition(|param| param.initial_value.is_none())
+            .map_or(0, |index| index + 1);
+
+        if args.len() < required || args.len() > function.parameters.len() {
+            let expected = if required == function.parameters.len() {
+                format!("{}", required)
+            } else {
+                format!("{} to {}", required, function.parameters.len())
+            };
+            return Err(LoaError::new(
+                LoaErrorKind::RuntimeError(RuntimeErrorKind::ArityMismatch(name.to_string())),
+                format!("Function '{}' expects {} argument(s), found {}", name, expected, args.len()),
+                current_file(),
+                0,
+                0,
+            ));
+        }
+
+        if self.call_depth >= self.recursion_limit {
+            self.abort(LoaError::new(
+                LoaErrorKind::RuntimeError(RuntimeErrorKind::RecursionLimit(name.to_string())),
+                format!("Calls to '{}' nested more than {} deep; check that the recursion stops", name, self.recursion_limit),
+                current_file(),
+                0,
+                0,
+            ));
+            return Ok(Value::None);
+        }
+
+        let mut args = args.into_iter();
+        let mut frame = Frame { variables: closure.captured.clone(), ..Frame::default() };
+        // Its own name, so a nested function can call itself
+        frame.variables.insert(name.clone(), Value::Function(Rc::clone(closure)));
+        for param in &function.parameters {
+            // Missing trailing arguments fall back to the parameter's default
+            let value = match args.next() {
+                Some(arg) => arg,
+                None => param.initial_value.as_ref().map(Value::from).unwrap_or(Value::None),
+            };
+            frame.variables.insert(param.name.clone(), value);
+        }
+
+        // Errors after the call point at the call again, not into the body
+        let position = self.position;
+        self.frames.push(frame);
+        self.call_depth += 1;
+        let flow = self.execute_block(&function.body);
+        self.call_depth -= 1;
+        self.frames.pop();
+        self.position = position;
+
+        match flow {
+            Flow::Return(value) => Ok(value),
+            // The interrupt flag stays set, so the caller stops right after
+            Flow::Normal | Flow::Break | Flow::Continue | Flow::Interrupted => Ok(Value::None),
+        }
+    }
+
+    fn execute_statement(&mut self, stmt: &StatementNode) -> Flow {
         match stmt {
-            StatementNode::PrintArgs(args) => {
+            StatementNode::PrintArgs { args, sep, end } => {
+                let mut text = Vec::with_capacity(args.len());
                 for expr in args {
-                    let value = self.evaluate_expression(expr);
-                    match value {
-                        Value::Number(n) => println!("{}", n),
-                        Value::Float(f) => println!("{}", f),
-                        Value::String(s) => println!("{}", s),
-                        Value::Bool(b) => println!("{}", b),
-                        Value::None => println!("None"),
-                    }
+                    text.push(self.evaluate_expression(expr).to_string());
                 }
+                let sep = sep.as_ref().map_or(" ".to_string(), |sep| self.evaluate_expression(sep).to_string());
+                let end = end.as_ref().map_or("\n".to_string(), |end| self.evaluate_expression(end).to_string());
+                // An argument that stopped the program has no value to show
+                if self.is_interrupted() {
+                    return Flow::Interrupted;
+                }
+                write!(self.output, "{}{}", text.join(&sep), end).ok();
+            }
+            StatementNode::Expression(expr) => {
+                self.evaluate_expression(expr);
             }
-            StatementNode::Assign { variable, value } => {
+            StatementNode::Assign { variable, value, line, column } => {
                 let val = self.evaluate_expression(value);
-                self.variables.insert(variable.clone(), val);
-            }
-            StatementNode::While { condition, body } => {
-                while let Value::Bool(true) = self.evaluate_expression(condition) {
-                    self.execute(body);
-                }
-            }
-            StatementNode::If { condition, body, else_if_blocks, else_block } => {
-                if self.evaluate_condition(condition) {
-                    self.execute(body);
-                } else if let Some(else_ifs) = else_if_blocks {
-                    let mut executed = false;
-
-                    for else_if in else_ifs.iter() {
-                        if let ASTNode::Statement(StatementNode::If { condition: else_if_condition, body, else_if_blocks: _, else_block: inner_else_block }) = else_if {
-                            if self.evaluate_condition(else_if_condition) {
-                                self.execute(body);
-                                executed = true;
-                                break;
-                            } else if let Some(inner_else_block) = inner_else_block {
-                                let warning = LoaError::new(
-                                    LoaErrorKind::SyntaxError("Unused else block".to_string()),
-                                    "Warning: An else block inside an else-if was ignored",
-                                    "unknown",
-                                    0,
-                                    0,
-                                );
-                                warning.display();
-                            }
+                if let Err(error) = self.set_variable(variable, val) {
+                    self.raise(LoaError { line: *line, column: *column, ..error });
+                }
+            }
+            StatementNode::IndexAssign { variable, index, value, line, column } => {
+                let index = self.evaluate_expression(index);
+                let value = self.evaluate_expression(value);
+                self.position = (*line, *column);
+                let result = match self.get_variable(variable) {
+                    Value::Array(mut items) => array_index(&items, &index)
+                        .map(|i| items[i] = value)
+                        .and_then(|()| self.set_variable(variable, Value::Array(items))),
+                    Value::Map(mut map) => match index {
+                        Value::String(key) => {
+                            map_insert(&mut map, key, value);
+                            self.set_variable(variable, Value::Map(map))
                         }
+                        other => Err(map_key_error(&other)),
+                    },
+                    other => Err(index_type_error(&other)),
+                };
+                if let Err(error) = result {
+                    self.raise(error);
+                }
+            }
+            StatementNode::MultiAssign { targets, values, line, column } => {
+                let values: Vec<Value> = values.iter().map(|value| self.evaluate_expression(value)).collect();
+                for (variable, val) in targets.iter().zip(values) {
+                    if let Err(error) = self.set_variable(variable, val) {
+                        self.raise(LoaError { line: *line, column: *column, ..error });
+                    }
+                }
+            }
+            StatementNode::ConditionalAssign { variable, operator, value, line, column } => {
+                let current = self.get_variable(variable);
+                // The right side is only evaluated when the assignment happens
+                let should_assign = match operator {
+                    Operator::NullCoalesceAssign => matches!(current, Value::None),
+                    Operator::LogicalOrAssign => !is_truthy(&current),
+                    _ => false,
+                };
+
+                if should_assign {
+                    let val = self.evaluate_expression(value);
+                    if let Err(error) = self.set_variable(variable, val) {
+                        self.raise(LoaError { line: *line, column: *column, ..error });
+                    }
+                }
+            }
+            StatementNode::While { condition, body, else_block } => {
+                while self.evaluate_condition(condition) {
+                    if self.is_interrupted() {
+                        return Flow::Interrupted;
                     }
+                    match self.execute_block(body) {
+                        Flow::Normal | Flow::Continue => {}
+                        // Leaving by `break` skips the else block
+                        Flow::Break => return Flow::Normal,
+                        flow => return flow,
+                    }
+                }
 
-                    if !executed {
-                        if let Some(else_if) = else_ifs.first() {
-                            if let ASTNode::Statement(StatementNode::If { else_block: Some(inner_else_block), .. }) = else_if {
-                                self.execute(inner_else_block);
+                if let Some(else_block) = else_block {
+                    return self.execute_block(else_block);
+                }
+            }
+            StatementNode::ForIn { var, iterable, body, line, column } => {
+                match self.evaluate_expression(iterable) {
+                    Value::Array(items) => {
+                        for item in items {
+                            if let Some(flow) = self.for_iteration(var, item, body) {
+                                return flow;
                             }
                         }
                     }
-                } else if let Some(else_block) = else_block {
-                    self.execute(else_block);
+                    // Elements are produced one at a time, so leaving early
+                    // never computes the rest of the sequence
+                    Value::Lazy(seq) => {
+                        for n in seq.start..seq.end {
+                            if self.is_interrupted() {
+                                return Flow::Interrupted;
+                            }
+                            match self.lazy_element(&seq, n) {
+                                Ok(Some(item)) => {
+                                    if let Some(flow) = self.for_iteration(var, item, body) {
+                                        return flow;
+                                    }
+                                }
+                                Ok(None) => {}
+                                Err(error) => {
+                                    self.raise(error);
+                                    return Flow::Normal;
+                                }
+                            }
+                        }
+                    }
+                    other => {
+                        self.raise(LoaError::new(
+                            LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError(var.clone())),
+                            format!("'for {} in' expects an array or a lazy sequence, found {}", var, other.type_name()),
+                            current_file(),
+                            *line,
+                            *column,
+                        ));
+                    }
                 }
             }
-            StatementNode::Break => {}
-            StatementNode::Continue => {}
-            StatementNode::Return(_) => {}
+            StatementNode::WhileLet { variable, value, body } => {
+                loop {
+                    let value = self.evaluate_expression(value);
+                    if matches!(value, Value::None) || self.is_interrupted() {
+                        break;
+                    }
+                    if let Err(error) = self.set_variable(variable, value) {
+                        self.raise(error);
+                        break;
+                    }
+                    match self.execute_block(body) {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => break,
+                        flow => return flow,
+                    }
+                }
+                if self.is_interrupted() {
+                    return Flow::Interrupted;
+                }
+            }
+            StatementNode::If { branches, else_block } => {
+                for (condition, body) in branches {
+                    if self.evaluate_condition(condition) {
+                        return self.execute_block(body);
+                    }
+                }
+
+                if let Some(else_block) = else_block {
+                    return self.execute_block(else_block);
+                }
+            }
+            StatementNode::Match { scrutinee, arms, default } => {
+                let value = self.evaluate_expression(scrutinee);
+                for (case, body) in arms {
+                    if self.evaluate_expression(case) == value {
+                        return self.execute_block(body);
+                    }
+                }
+
+                if let Some(default) = default {
+                    return self.execute_block(default);
+                }
+            }
+            StatementNode::Import { path, once, line, column } => {
+                if let Err(error) = self.import_module(path, *once) {
+                    self.position = (*line, *column);
+                    self.raise(error);
+                }
+            }
+            StatementNode::Break => return Flow::Break,
+            StatementNode::Continue => return Flow::Continue,
+            StatementNode::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.evaluate_expression(expr),
+                    None => Value::None,
+                };
+                return Flow::Return(value);
+            }
             _ => {}
         }
+
+        Flow::Normal
     }
 
-    fn evaluate_condition(&mut self, expr: &Expression) -> bool {
-        match self.evaluate_expression(expr) {
-            Value::Bool(b) => b,
-            Value::Number(n) => n != 0,
-            _ => false,
+    // Runs a `for` body once with `var` bound to `item`; `Some` ends the
+    // loop with that flow
+    fn for_iteration(&mut self, var: &str, item: Value, body: &[ASTNode]) -> Option<Flow> {
+        if self.is_interrupted() {
+            return Some(Flow::Interrupted);
         }
+        if let Err(error) = self.declare_variable(var, item, false) {
+            self.raise(error);
+            return Some(Flow::Normal);
+        }
+        match self.execute_block(body) {
+            Flow::Normal | Flow::Continue => None,
+            Flow::Break => Some(Flow::Normal),
+            flow => Some(flow),
+        }
+    }
+
+    fn evaluate_condition(&mut self, expr: &Expression) -> bool {
+        is_truthy(&self.evaluate_expression(expr))
     }
 
     fn evaluate_expression(&mut self, expr: &Expression) -> Value {
@@ -115,33 +863,351 @@ impl Interpreter {
                 Literal::Number(n) => Value::Number(*n),
                 Literal::Float(f) => Value::Float(*f),
                 Literal::String(s) => Value::String(s.clone()),
+                Literal::Bool(b) => Value::Bool(*b),
+                Literal::None => Value::None,
             },
-            Expression::Variable(name) => {
-                self.variables.get(name).cloned().unwrap_or(Value::None)
+            Expression::Interpolation(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        InterpolationPart::Text(literal) => text.push_str(literal),
+                        InterpolationPart::Expression(expr) => text.push_str(&self.evaluate_expression(expr).to_string()),
+                    }
+                }
+                Value::String(text)
+            }
+            Expression::ArrayLiteral(elements) => {
+                Value::Array(elements.iter().map(|element| self.evaluate_expression(element)).collect())
+            }
+            Expression::MapLiteral(entries) => {
+                let mut map = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    let key = self.evaluate_expression(key);
+                    let value = self.evaluate_expression(value);
+                    match key {
+                        Value::String(key) => map_insert(&mut map, key, value),
+                        other => {
+                            self.raise(map_key_error(&other));
+                            return Value::None;
+                        }
+                    }
+                }
+                Value::Map(map)
+            }
+            Expression::Index { target, index, line, column } => {
+                let target = self.evaluate_expression(target);
+                let index = self.evaluate_expression(index);
+                self.position = (*line, *column);
+                index_value(target, &index).unwrap_or_else(|error| {
+                    self.raise(error);
+                    Value::None
+                })
+            }
+            Expression::Variable(name) => self.get_variable(name),
+            Expression::Grouped(inner) => self.evaluate_expression(inner),
+            Expression::FunctionCall { name, args, line, column } => {
+                match self.call_function(name, args, (*line, *column)) {
+                    Ok(value) => value,
+                    Err(error) => {
+                        self.raise(error);
+                        Value::None
+                    }
+                }
+            }
+            Expression::MethodCall { receiver, method, args, line, column } => {
+                match self.call_method(receiver, method, args, (*line, *column)) {
+                    Ok(value) => value,
+                    Err(error) => {
+                        self.raise(error);
+                        Value::None
+                    }
+                }
+            }
+            // `&&` and `||` skip their right side once the left decides the result
+            Expression::BinaryExpression { left, operator: operator @ (Operator::LogicalAnd | Operator::LogicalOr), right, .. } => {
+                let l = self.evaluate_condition(left);
+                match operator {
+                    Operator::LogicalAnd if !l => Value::Bool(false),
+                    Operator::LogicalOr if l => Value::Bool(true),
+                    _ => Value::Bool(self.evaluate_condition(right)),
+                }
             }
-            Expression::BinaryExpression { left, operator, right } => {
+            Expression::BinaryExpression { left, operator, right, line, column } => {
                 let l = self.evaluate_expression(left);
                 let r = self.evaluate_expression(right);
+                self.position = (*line, *column);
                 self.evaluate_binary_op(l, operator, r)
             }
+            Expression::UnaryExpression { operator: Operator::Not, operand, .. } => {
+                Value::Bool(!self.evaluate_condition(operand))
+            }
+            Expression::UnaryExpression { operator: Operator::Subtract, operand, line, column } => {
+                let value = self.evaluate_expression(operand);
+                self.position = (*line, *column);
+                match value {
+                    Value::Number(n) => self.integer_result(&Operator::Subtract, n.checked_neg(), n.wrapping_neg()),
+                    Value::Float(x) => Value::Float(-x),
+                    _ => Value::None,
+                }
+            }
             _ => Value::None,
         }
     }
 
-    fn evaluate_binary_op(&self, l: Value, op: &Operator, r: Value) -> Value {
+    // `checked` is None when the exact result doesn't fit in an i64
+    fn integer_result(&mut self, op: &Operator, checked: Option<i64>, wrapped: i64) -> Value {
+        match checked {
+            Some(n) => Value::Number(n),
+            None if self.wrapping_arithmetic => Value::Number(wrapped),
+            None => {
+                self.raise(LoaError::new(
+                    LoaErrorKind::RuntimeError(RuntimeErrorKind::IntegerOverflow(op.symbol().to_string())),
+                    format!("Integer overflow in '{}'; the result does not fit in 64 bits", op.symbol()),
+                    current_file(),
+                    0,
+                    0,
+                ));
+                Value::None
+            }
+        }
+    }
+
+    // Numeric rules: integer op integer stays an integer, so `/` truncates
+    // toward zero (7 / 2 is 3, -7 / 2 is -3); if either side is a float both
+    // are promoted and the result is a float, even when it is integral
+    // (4.0 / 2.0 is 2.0, 7 / 2.0 is 3.5). `~/` rounds toward negative
+    // infinity instead (-7 ~/ 2 is -4, 7.5 ~/ 2 is 3.0)
+    fn evaluate_binary_op(&mut self, l: Value, op: &Operator, r: Value) -> Value {
+        if let Operator::Xor = op {
+            return evaluate_xor(l, r).unwrap_or_else(|error| {
+                self.raise(error);
+                Value::None
+            });
+        }
+        if let Operator::Equal | Operator::NotEqual = op {
+            return Value::Bool((l == r) == matches!(op, Operator::Equal));
+        }
+
         match (l, r) {
             (Value::Number(a), Value::Number(b)) => match op {
-                Operator::Add => Value::Number(a + b),
-                Operator::Subtract => Value::Number(a - b),
-                Operator::Multiply => Value::Number(a * b),
-                Operator::Divide => Value::Number(a / b),
-                Operator::Less => Value::Bool(a < b),
-                Operator::Greater => Value::Bool(a > b),
-                Operator::Equal => Value::Bool(a == b),
-                Operator::NotEqual => Value::Bool(a != b),
-                _ => Value::None,
+                Operator::Add => self.integer_result(op, a.checked_add(b), a.wrapping_add(b)),
+                Operator::Subtract => self.integer_result(op, a.checked_sub(b), a.wrapping_sub(b)),
+                Operator::Multiply => self.integer_result(op, a.checked_mul(b), a.wrapping_mul(b)),
+                Operator::Divide | Operator::FloorDivide | Operator::Remainder if b == 0 => {
+                    self.raise(LoaError::new(
+                        LoaErrorKind::RuntimeError(RuntimeErrorKind::DivideByZero),
+                        "Division by zero",
+                        current_file(),
+                        0,
+                        0,
+                    ));
+                    Value::None
+                }
+                // Only i64::MIN / -1 overflows; its remainder is 0
+                Operator::Divide => self.integer_result(op, a.checked_div(b), a.wrapping_div(b)),
+                Operator::FloorDivide => match self.integer_result(op, a.checked_div(b), a.wrapping_div(b)) {
+                    Value::Number(quotient) if a.wrapping_rem(b) != 0 && (a < 0) != (b < 0) => Value::Number(quotient - 1),
+                    quotient => quotient,
+                },
+                Operator::Remainder => Value::Number(a.wrapping_rem(b)),
+                // A negative exponent or an overflowing result promotes to float
+                Operator::Power => match u32::try_from(b).ok().and_then(|exp| a.checked_pow(exp)) {
+                    Some(n) => Value::Number(n),
+                    None => Value::Float((a as f64).powf(b as f64)),
+                },
+                op => compare(&a, op, &b).map_or(Value::None, Value::Bool),
             },
+            (Value::Float(a), Value::Float(b)) => evaluate_float_op(a, op, b),
+            (Value::Number(a), Value::Float(b)) => evaluate_float_op(a as f64, op, b),
+            (Value::Float(a), Value::Number(b)) => evaluate_float_op(a, op, b as f64),
+            // Strings order lexicographically, by Unicode scalar value
+            (Value::String(a), Value::String(b)) => compare(&a, op, &b).map_or(Value::None, Value::Bool),
+            (Value::Array(mut a), Value::Array(b)) if matches!(op, Operator::Add) => {
+                a.extend(b);
+                Value::Array(a)
+            }
+            (l @ Value::Array(_), r) | (l, r @ Value::Array(_)) if matches!(op, Operator::Add) => {
+                self.raise(LoaError::new(
+                    LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError("+".to_string())),
+                    format!("Operator '+' joins two arrays, found {} and {}; use push() to add an element", l.type_name(), r.type_name()),
+                    current_file(),
+                    0,
+                    0,
+                ));
+                Value::None
+            }
+            (l @ (Value::String(_) | Value::Bool(_) | Value::None | Value::Array(_)), r)
+            | (l, r @ (Value::String(_) | Value::Bool(_) | Value::None | Value::Array(_))) if op.is_comparison() => {
+                self.raise(comparison_error(op, &l, &r));
+                Value::None
+            }
             _ => Value::None,
         }
     }
 }
+
+pub(crate) fn map_get<'a>(map: &'a [(String, Value)], key: &str) -> Option<&'a Value> {
+    map.iter().find(|(name, _)| name == key).map(|(_, value)| value)
+}
+
+// Replaces the value of an existing key in place, so it keeps its position
+pub(crate) fn map_insert(map: &mut Vec<(String, Value)>, key: String, value: Value) {
+    match map.iter_mut().find(|(name, _)| *name == key) {
+        Some((_, slot)) => *slot = value,
+        None => map.push((key, value)),
+    }
+}
+
+// `target[index]`: an array element, a character of a string or a map value
+fn index_value(target: Value, index: &Value) -> Result<Value, LoaError> {
+    match target {
+        Value::Array(mut items) => array_index(&items, index).map(|i| items.swap_remove(i)),
+        Value::String(s) => {
+            let chars: Vec<char> = s.chars().collect();
+            let i = checked_index(chars.len(), index, "string")?;
+            Ok(Value::String(chars[i].to_string()))
+        }
+        Value::Map(map) => match index {
+            Value::String(key) => map_get(&map, key).cloned().ok_or_else(|| LoaError::new(
+                LoaErrorKind::RuntimeError(RuntimeErrorKind::KeyNotFound(key.clone())),
+                format!("Key {:?} is not in the map", key),
+                current_file(),
+                0,
+                0,
+            )),
+            other => Err(map_key_error(other)),
+        },
+        other => Err(index_type_error(&other)),
+    }
+}
+
+fn array_index(items: &[Value], index: &Value) -> Result<usize, LoaError> {
+    checked_index(items.len(), index, "array")
+}
+
+fn checked_index(len: usize, index: &Value, kind: &str) -> Result<usize, LoaError> {
+    let Value::Number(n) = index else {
+        return Err(LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError("[]".to_string())),
+            format!("An {} index must be a number, found {}", kind, index.type_name()),
+            current_file(),
+            0,
+            0,
+        ));
+    };
+    usize::try_from(*n).ok().filter(|&i| i < len).ok_or_else(|| LoaError::new(
+        LoaErrorKind::RuntimeError(RuntimeErrorKind::IndexOutOfBounds(n.to_string())),
+        format!("Index {} is out of bounds for {} of length {}", n, kind, len),
+        current_file(),
+        0,
+        0,
+    ))
+}
+
+fn map_key_error(key: &Value) -> LoaError {
+    LoaError::new(
+        LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError("[]".to_string())),
+        format!("Map keys are strings, found {}", key.type_name()),
+        current_file(),
+        0,
+        0,
+    )
+}
+
+fn index_type_error(target: &Value) -> LoaError {
+    LoaError::new(
+        LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError("[]".to_string())),
+        format!("Only arrays, strings and maps can be indexed, found {}", target.type_name()),
+        current_file(),
+        0,
+        0,
+    )
+}
+
+fn comparison_error(op: &Operator, l: &Value, r: &Value) -> LoaError {
+    LoaError::new(
+        LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError(op.symbol().to_string())),
+        format!("Operator '{}' cannot compare {} with {}", op.symbol(), l.type_name(), r.type_name()),
+        current_file(),
+        0,
+        0,
+    )
+}
+
+fn interrupted_error() -> LoaError {
+    LoaError::new(
+        LoaErrorKind::RuntimeError(RuntimeErrorKind::Interrupted),
+        "Execution interrupted",
+        current_file(),
+        0,
+        0,
+    )
+}
+
+// The comparison operators; None for any other operator
+fn compare<T: PartialOrd + ?Sized>(a: &T, op: &Operator, b: &T) -> Option<bool> {
+    match op {
+        Operator::Less => Some(a < b),
+        Operator::Greater => Some(a > b),
+        Operator::LessEqual => Some(a <= b),
+        Operator::GreaterEqual => Some(a >= b),
+        Operator::Equal => Some(a == b),
+        Operator::NotEqual => Some(a != b),
+        _ => None,
+    }
+}
+
+fn assign_to_const(name: &str) -> LoaError {
+    LoaError::new(
+        LoaErrorKind::RuntimeError(RuntimeErrorKind::AssignToConst(name.to_string())),
+        format!("Cannot assign to '{}', it is declared const", name),
+        current_file(),
+        0,
+        0,
+    )
+}
+
+// Bitwise on integers, logical on booleans
+fn evaluate_xor(l: Value, r: Value) -> Result<Value, LoaError> {
+    match (l, r) {
+        (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a ^ b)),
+        (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a ^ b)),
+        (l, r) => Err(LoaError::new(
+            LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError("^".to_string())),
+            format!("Operator '^' expects two integers or two booleans, found {} and {}", l.type_name(), r.type_name()),
+            current_file(),
+            0,
+            0,
+        )),
+    }
+}
+
+// Mixed integer/float operands are promoted to float before reaching here
+fn evaluate_float_op(a: f64, op: &Operator, b: f64) -> Value {
+    match op {
+        Operator::Add => Value::Float(a + b),
+        Operator::Subtract => Value::Float(a - b),
+        Operator::Multiply => Value::Float(a * b),
+        Operator::Divide => Value::Float(a / b),
+        Operator::FloorDivide => Value::Float((a / b).floor()),
+        Operator::Remainder => Value::Float(a % b),
+        Operator::Power => Value::Float(a.powf(b)),
+        op => compare(&a, op, &b).map_or(Value::None, Value::Bool),
+    }
+}
+
+// As in Python: false, zero, empty strings, arrays and maps, and None are
+// falsy. Functions and lazy sequences are always truthy, since a lazy
+// sequence can only be found empty by running it
+pub(crate) fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Number(n) => *n != 0,
+        Value::Float(x) => *x != 0.0,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Map(entries) => !entries.is_empty(),
+        Value::Function(_) | Value::Lazy(_) => true,
+        Value::None => false,
+    }
+}
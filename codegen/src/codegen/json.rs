@@ -0,0 +1,207 @@
+use std::iter::Peekable;
+use std::str::Chars;
+use crate::codegen::map_insert;
+use crate::Value;
+
+// A parsed JSON document
+#[derive(Debug, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    // Follows a dotted path such as "server.ports.0"; numbers index arrays
+    pub fn get(&self, path: &str) -> Option<&Json> {
+        path.split('.')
+            .filter(|key| !key.is_empty())
+            .try_fold(self, |json, key| match json {
+                Json::Object(fields) => fields.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+                Json::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+                _ => None,
+            })
+    }
+
+    // Objects become maps; a repeated key keeps its last value
+    pub fn to_value(&self) -> Value {
+        match self {
+            Json::Null => Value::None,
+            Json::Bool(b) => Value::Bool(*b),
+            Json::Number(n) => Value::Number(*n),
+            Json::Float(x) => Value::Float(*x),
+            Json::String(s) => Value::String(s.clone()),
+            Json::Array(items) => Value::Array(items.iter().map(Json::to_value).collect()),
+            Json::Object(fields) => {
+                let mut map = Vec::with_capacity(fields.len());
+                for (key, value) in fields {
+                    map_insert(&mut map, key.clone(), value.to_value());
+                }
+                Value::Map(map)
+            }
+        }
+    }
+}
+
+pub fn parse_json(text: &str) -> Result<Json, String> {
+    let mut chars = text.chars().peekable();
+    let json = parse_value(&mut chars)?;
+
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(json),
+        Some(c) => Err(format!("unexpected '{}' after the value", c)),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    skip_whitespace(chars);
+
+    match chars.peek() {
+        Some('{') => parse_object(chars),
+        Some('[') => parse_array(chars),
+        Some('"') => parse_string(chars).map(Json::String),
+        Some(c) if *c == '-' || c.is_ascii_digit() => parse_number(chars),
+        Some(c) if c.is_ascii_alphabetic() => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "null" => Ok(Json::Null),
+                "true" => Ok(Json::Bool(true)),
+                "false" => Ok(Json::Bool(false)),
+                _ => Err(format!("unknown literal '{}'", word)),
+            }
+        }
+        Some(c) => Err(format!("unexpected '{}'", c)),
+        None => Err("unexpected end of input".to_string()),
+    }
+}
+
+fn parse_object(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    chars.next(); // consume '{'
+
+    let mut fields = vec![];
+    skip_whitespace(chars);
+    if chars.next_if_eq(&'}').is_some() {
+        return Ok(Json::Object(fields));
+    }
+
+    loop {
+        skip_whitespace(chars);
+        if chars.peek() != Some(&'"') {
+            return Err("expected a string key in object".to_string());
+        }
+        let key = parse_string(chars)?;
+
+        skip_whitespace(chars);
+        if chars.next() != Some(':') {
+            return Err(format!("expected ':' after key \"{}\"", key));
+        }
+        fields.push((key, parse_value(chars)?));
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(Json::Object(fields)),
+            _ => return Err("expected ',' or '}' in object".to_string()),
+        }
+    }
+}
+
+fn parse_array(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    chars.next(); // consume '['
+
+    let mut items = vec![];
+    skip_whitespace(chars);
+    if chars.next_if_eq(&']').is_some() {
+        return Ok(Json::Array(items));
+    }
+
+    loop {
+        items.push(parse_value(chars)?);
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some(']') => return Ok(Json::Array(items)),
+            _ => return Err("expected ',' or ']' in array".to_string()),
+        }
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    chars.next(); // consume '"'
+
+    let mut s = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(s),
+            Some('\\') => match chars.next() {
+                Some('n') => s.push('\n'),
+                Some('t') => s.push('\t'),
+                Some('r') => s.push('\r'),
+                Some('b') => s.push('\u{8}'),
+                Some('f') => s.push('\u{c}'),
+                Some('u') => s.push(parse_unicode_escape(chars)?),
+                Some(c @ ('"' | '\\' | '/')) => s.push(c),
+                Some(c) => return Err(format!("invalid escape '\\{}'", c)),
+                None => return Err("unterminated string".to_string()),
+            },
+            Some(c) => s.push(c),
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+// The character of a `\uXXXX` escape, whose `\u` is already consumed.
+// Characters outside the Basic Multilingual Plane are written as a UTF-16
+// surrogate pair, e.g. "\ud83d\ude00", and combine into one char
+fn parse_unicode_escape(chars: &mut Peekable<Chars>) -> Result<char, String> {
+    let unit = parse_hex4(chars)?;
+    let code = match unit {
+        0xD800..=0xDBFF => {
+            if chars.next() != Some('\\') || chars.next() != Some('u') {
+                return Err(format!("high surrogate '\\u{:04x}' is not followed by a low surrogate", unit));
+            }
+            let low = parse_hex4(chars)?;
+            if !(0xDC00..=0xDFFF).contains(&low) {
+                return Err(format!("high surrogate '\\u{:04x}' is followed by '\\u{:04x}', not a low surrogate", unit, low));
+            }
+            0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
+        }
+        _ => unit,
+    };
+    char::from_u32(code).ok_or_else(|| format!("lone surrogate '\\u{:04x}'", code))
+}
+
+fn parse_hex4(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+    let hex: String = chars.by_ref().take(4).collect();
+    match u32::from_str_radix(&hex, 16) {
+        Ok(unit) if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(unit),
+        _ => Err(format!("invalid escape '\\u{}'", hex)),
+    }
+}
+
+fn parse_number(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+    let mut text = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+        text.push(c);
+    }
+
+    if let Ok(n) = text.parse::<i64>() {
+        return Ok(Json::Number(n));
+    }
+    text.parse::<f64>()
+        .map(Json::Float)
+        .map_err(|_| format!("invalid number '{}'", text))
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+use std::rc::Rc;
+use ::error::LoaError;
+use crate::{Closure, Interpreter, Value};
+use crate::codegen::codegen::is_truthy;
+
+// A sequence whose elements are only computed while it is consumed, so
+// `take(map(lazy_range(0, 1000000000), f), 3)` calls `f` three times
+#[derive(Debug, Clone)]
+pub struct LazySeq {
+    pub start: i64,
+    pub end: i64,
+    pub stages: Vec<LazyStage>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LazyStage {
+    Map(Rc<Closure>),
+    Filter(Rc<Closure>),
+}
+
+impl LazySeq {
+    pub fn range(start: i64, end: i64) -> Self {
+        LazySeq { start, end, stages: Vec::new() }
+    }
+
+    // A new sequence with one more stage; the source range is shared by value
+    pub fn with_stage(&self, stage: LazyStage) -> Self {
+        let mut stages = self.stages.clone();
+        stages.push(stage);
+        LazySeq { start: self.start, end: self.end, stages }
+    }
+}
+
+impl fmt::Display for LazySeq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<lazy range({}, {})", self.start, self.end)?;
+        for stage in &self.stages {
+            match stage {
+                LazyStage::Map(closure) => write!(f, " | map {}", closure.function.name)?,
+                LazyStage::Filter(closure) => write!(f, " | filter {}", closure.function.name)?,
+            }
+        }
+        write!(f, ">")
+    }
+}
+
+impl Interpreter {
+    // Runs the pipeline until `limit` elements survive it (or the range ends)
+    pub(crate) fn collect_lazy(&mut self, seq: &LazySeq, limit: Option<usize>) -> Result<Vec<Value>, LoaError> {
+        let mut items = Vec::new();
+
+        for n in seq.start..seq.end {
+            if limit.is_some_and(|limit| items.len() >= limit) || self.is_interrupted() {
+                break;
+            }
+            if let Some(value) = self.lazy_element(seq, n)? {
+                items.push(value);
+            }
+        }
+
+        Ok(items)
+    }
+
+    // Passes the source element `n` through every stage; `None` when a
+    // filter drops it
+    pub(crate) fn lazy_element(&mut self, seq: &LazySeq, n: i64) -> Result<Option<Value>, LoaError> {
+        let mut value = Value::Number(n);
+        for stage in &seq.stages {
+            match stage {
+                LazyStage::Map(function) => {
+                    value = self.call_closure(function, vec![value])?;
+                }
+                LazyStage::Filter(function) => {
+                    if !is_truthy(&self.call_closure(function, vec![value.clone()])?) {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+        Ok(Some(value))
+    }
+}
@@ -1,3 +1,10 @@
+#[allow(clippy::module_inception)]
 mod codegen;
+mod builtins;
+mod lazy;
+mod json;
 
-pub use codegen::*;
\ No newline at end of file
+pub use codegen::*;
+pub use builtins::{BuiltinInfo, BUILTINS};
+pub use lazy::{LazySeq, LazyStage};
+pub use json::{parse_json, Json};
\ No newline at end of file
@@ -0,0 +1,119 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use codegen::{Interpreter, Value};
+use error::{LoaErrorKind, RuntimeErrorKind};
+use lexer::Lexer;
+use parser::ast::{ASTNode, Expression, StatementNode};
+use parser::parse;
+
+// A writer the test can still read after handing a clone to the interpreter
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn parse_source(source: &str) -> Vec<ASTNode> {
+    parse(&Lexer::new(source).tokenize()).unwrap_or_else(|errors| panic!("{:?}", errors))
+}
+
+fn parse_expression(source: &str) -> Expression {
+    match parse_source(source).pop() {
+        Some(ASTNode::Statement(StatementNode::Expression(expr))) => expr,
+        other => panic!("not an expression: {:?}", other),
+    }
+}
+
+#[test]
+fn run_returns_the_first_error_and_stops() {
+    let output = SharedOutput::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    let ast = parse_source("print(\"before\")\nx = 1 / 0\nprint(\"after\")\ny = upper(5)\n");
+
+    let error = interpreter.run(&ast).unwrap_err();
+    assert!(matches!(error.kind, LoaErrorKind::RuntimeError(RuntimeErrorKind::DivideByZero)), "{:?}", error.kind);
+    assert_eq!(error.line, 2);
+    assert_eq!(output.text(), "before\n");
+
+    // The next run starts clean
+    assert!(interpreter.run(&parse_source("print(\"again\")\n")).is_ok());
+    assert_eq!(output.text(), "before\nagain\n");
+}
+
+#[test]
+fn print_and_input_prompts_go_to_the_output_writer() {
+    let output = SharedOutput::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    interpreter.set_input(io::Cursor::new("Ada\n"));
+    let ast = parse_source("print(\"a\", 1, sep=\"-\")\nname = input(\"name? \")\nprint(\"hi\", name, end=\"!\")\n");
+
+    assert!(interpreter.run(&ast).is_ok());
+    assert_eq!(output.0.borrow().as_slice(), b"a-1\nname? hi Ada!");
+}
+
+#[test]
+fn evaluate_returns_the_value() {
+    let mut interpreter = Interpreter::with_output(SharedOutput::default());
+    assert!(interpreter.run(&parse_source("x = 4\n")).is_ok());
+
+    let value = interpreter.evaluate(&parse_expression("x * 10 + 2")).unwrap();
+    assert!(matches!(value, Value::Number(42)), "{:?}", value);
+    let value = interpreter.evaluate(&parse_expression("[x, \"y\"]")).unwrap();
+    assert_eq!(value.to_string(), "[4, \"y\"]");
+}
+
+#[test]
+fn evaluate_returns_the_first_error() {
+    let output = SharedOutput::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+
+    let error = interpreter.evaluate(&parse_expression("[upper(5), 1 / 0]")).unwrap_err();
+    assert!(matches!(error.kind, LoaErrorKind::RuntimeError(RuntimeErrorKind::TypeError(_))), "{:?}", error.kind);
+    assert_eq!(output.text(), "", "errors are returned, not printed");
+
+    // A failed evaluation leaves the interpreter usable
+    assert!(matches!(interpreter.evaluate(&parse_expression("1 + 1")), Ok(Value::Number(2))));
+}
+
+#[test]
+fn or_assign_and_filter_keep_truthy_values() {
+    let output = SharedOutput::default();
+    let mut interpreter = Interpreter::with_output(output.clone());
+    let ast = parse_source(concat!(
+        "name = \"Ada\"\nname ||= \"anonymous\"\n",
+        "ratio = 0.5\nratio ||= 1.0\n",
+        "items = [0]\nitems ||= [1, 2]\n",
+        "empty = \"\"\nempty ||= \"filled\"\n",
+        "zero = 0.0\nzero ||= 2.5\n",
+        "unset = []\nunset ||= {\"a\": 1}\n",
+        "print(name, ratio, items, empty, zero, unset)\n",
+        "fun same(x):\n    return x\n",
+        "print(filter([\"\", \"a\", [], [0], 0.0, 1.5, {}], same))\n",
+    ));
+
+    assert!(interpreter.run(&ast).is_ok());
+    assert_eq!(output.text(), "Ada 0.5 [0] filled 2.5 {\"a\": 1}\n[\"a\", [0], 1.5]\n");
+}
+
+#[test]
+fn abs_of_the_smallest_integer_overflows() {
+    let mut interpreter = Interpreter::with_output(SharedOutput::default());
+    let error = interpreter.evaluate(&parse_expression("abs(-9223372036854775807 - 1)")).unwrap_err();
+    assert!(matches!(&error.kind, LoaErrorKind::RuntimeError(RuntimeErrorKind::IntegerOverflow(op)) if op == "abs"), "{:?}", error.kind);
+    assert_eq!(error.message, "Integer overflow in 'abs'; the result does not fit in 64 bits");
+}
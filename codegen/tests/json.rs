@@ -0,0 +1,27 @@
+use codegen::{parse_json, Json};
+
+fn parse_string(text: &str) -> Result<String, String> {
+    match parse_json(text)? {
+        Json::String(s) => Ok(s),
+        other => panic!("not a string: {:?}", other),
+    }
+}
+
+#[test]
+fn unicode_escapes_decode() {
+    assert_eq!(parse_string(r#""caf\u00e9 \u2603""#).unwrap(), "caf\u{e9} \u{2603}");
+}
+
+#[test]
+fn surrogate_pairs_combine_into_one_char() {
+    assert_eq!(parse_string(r#""\ud83d\ude00""#).unwrap(), "\u{1f600}");
+    assert_eq!(parse_string(r#""a\uD834\uDD1Eb""#).unwrap(), "a\u{1d11e}b");
+}
+
+#[test]
+fn unpaired_surrogates_are_rejected() {
+    assert!(parse_string(r#""\ud83d""#).is_err());
+    assert!(parse_string(r#""\ud83dx""#).is_err());
+    assert!(parse_string(r#""\ud83dA""#).is_err());
+    assert!(parse_string(r#""\ude00""#).is_err());
+}
@@ -1,20 +1,149 @@
+use std::cell::RefCell;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+thread_local! {
+    // Name shown in diagnostics for the source currently being processed
+    static CURRENT_FILE: RefCell<String> = RefCell::new("unknown".to_string());
+    // Its text, so diagnostics can quote the offending line
+    static CURRENT_SOURCE: RefCell<Rc<str>> = RefCell::new(Rc::from(""));
+}
+
+pub fn set_current_file(name: impl Into<String>) {
+    CURRENT_FILE.with(|file| *file.borrow_mut() = name.into());
+}
+
+pub fn current_file() -> String {
+    CURRENT_FILE.with(|file| file.borrow().clone())
+}
+
+pub fn set_current_source(source: impl Into<Rc<str>>) {
+    CURRENT_SOURCE.with(|current| *current.borrow_mut() = source.into());
+}
+
+pub fn current_source() -> Rc<str> {
+    CURRENT_SOURCE.with(|source| Rc::clone(&source.borrow()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Always,
+    Auto,
+    Never,
+}
+
+// Process-wide, set once from `--color` before anything is printed
+static COLOR_CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+pub fn set_color_choice(choice: ColorChoice) {
+    COLOR_CHOICE.store(choice as u8, Ordering::Relaxed);
+}
+
+// `auto` colors only when neither stdout nor stderr is redirected and the
+// NO_COLOR environment variable (https://no-color.org) is unset or empty
+pub fn color_enabled() -> bool {
+    match COLOR_CHOICE.load(Ordering::Relaxed) {
+        choice if choice == ColorChoice::Always as u8 => true,
+        choice if choice == ColorChoice::Never as u8 => false,
+        _ => env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            && io::stdout().is_terminal() && io::stderr().is_terminal(),
+    }
+}
+
+fn paint(text: &str, rgb: &str) -> String {
+    if color_enabled() {
+        format!("\x1b[38;2;{}m{}\x1b[0m", rgb.replace(',', ";"), text)
+    } else {
+        text.to_string()
+    }
+}
+
 #[derive(Debug)]
 pub enum LoaErrorKind {
     UnexpectedToken(String),
     ExpectedToken(String),
     UnexpectedChar(char),
     SyntaxError(String),
+    IndentationError,
+    RuntimeError(RuntimeErrorKind),
 }
 
+// Errors raised while a program runs, as opposed to while it is parsed.
+// The String carries the operator or function involved.
+#[derive(Debug)]
+pub enum RuntimeErrorKind {
+    DivideByZero,
+    UndefinedVariable(String),
+    TypeError(String),
+    IndexOutOfBounds(String),
+    ArityMismatch(String),
+    AssignToConst(String),
+    IntegerOverflow(String),
+    RecursionLimit(String),
+    KeyNotFound(String),
+    InvalidArgument(String),
+    ParseError(String),
+    ImportError(String),
+    Interrupted,
+}
+
+impl RuntimeErrorKind {
+    // Shown as `error[<code>]` so a diagnostic can be looked up
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeErrorKind::DivideByZero => "DivideByZero",
+            RuntimeErrorKind::UndefinedVariable(_) => "UndefinedVariable",
+            RuntimeErrorKind::TypeError(_) => "TypeError",
+            RuntimeErrorKind::IndexOutOfBounds(_) => "IndexOutOfBounds",
+            RuntimeErrorKind::ArityMismatch(_) => "ArityMismatch",
+            RuntimeErrorKind::AssignToConst(_) => "AssignToConst",
+            RuntimeErrorKind::IntegerOverflow(_) => "IntegerOverflow",
+            RuntimeErrorKind::RecursionLimit(_) => "RecursionLimit",
+            RuntimeErrorKind::KeyNotFound(_) => "KeyNotFound",
+            RuntimeErrorKind::InvalidArgument(_) => "InvalidArgument",
+            RuntimeErrorKind::ParseError(_) => "ParseError",
+            RuntimeErrorKind::ImportError(_) => "ImportError",
+            RuntimeErrorKind::Interrupted => "Interrupted",
+        }
+    }
+}
+
+// What each `error[<code>]` means, for `loa explain <code>`
+pub const RUNTIME_ERROR_CODES: &[(&str, &str)] = &[
+    ("DivideByZero", "An integer was divided by zero."),
+    ("UndefinedVariable", "A name was used before anything was assigned to it."),
+    ("TypeError", "An operator or function received a value of the wrong type."),
+    ("IndexOutOfBounds", "An index was outside the bounds of the array or string."),
+    ("ArityMismatch", "A function was called with the wrong number of arguments."),
+    ("AssignToConst", "A name declared with 'const' was assigned or declared again."),
+    ("IntegerOverflow", "An integer result did not fit in 64 bits; use floats for larger values."),
+    ("RecursionLimit", "Function calls nested deeper than the interpreter allows, usually from recursion that never stops."),
+    ("KeyNotFound", "A map was read at a key it does not contain."),
+    ("InvalidArgument", "A function received an argument of the right type but an unusable value, such as a zero step."),
+    ("ParseError", "int() or float() was given a string that is not a number."),
+    ("ImportError", "A module could not be imported: the file is missing, unreadable or invalid, or imports itself."),
+    ("Interrupted", "The program was stopped before it finished, e.g. by Ctrl-C."),
+];
+
+pub fn explain(code: &str) -> Option<&'static str> {
+    RUNTIME_ERROR_CODES.iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, description)| *description)
+}
+
+// The file name, source line and label are `Box<str>`, a word smaller than
+// `String`, so `Result<_, LoaError>` stays under clippy's `result_large_err` limit
 #[derive(Debug)]
 pub struct LoaError {
     pub kind: LoaErrorKind,
     pub message: String,
-    pub file: String,
+    pub file: Box<str>,
     pub line: usize,
     pub column: usize,
-    pub source: Option<String>,
-    pub label: Option<String>,
+    pub source: Option<Box<str>>,
+    pub label: Option<Box<str>>,
 }
 
 impl LoaError {
@@ -22,7 +151,7 @@ impl LoaError {
         Self {
             kind,
             message: message.into(),
-            file: file.into(),
+            file: file.into().into_boxed_str(),
             line,
             column,
             source: None,
@@ -31,30 +160,50 @@ impl LoaError {
     }
 
     pub fn with_source(mut self, source: impl Into<String>) -> Self {
-        self.source = Some(source.into());
+        self.source = Some(source.into().into_boxed_str());
         self
     }
 
     pub fn with_label(mut self, label: impl Into<String>) -> Self {
-        self.label = Some(label.into());
+        self.label = Some(label.into().into_boxed_str());
         self
     }
 
     pub fn display(&self) {
-        eprintln!("error: {}", self.message);
-        eprintln!("  --> {}:{}:{}", self.file, self.line, self.column);
+        let header = match &self.kind {
+            LoaErrorKind::RuntimeError(kind) => format!("error[{}]:", kind.code()),
+            _ => "error:".to_string(),
+        };
+        eprintln!("{} {}", paint(&header, "255,71,71"), self.message);
+        // Lines are 1-based; 0 means the error has no position. An error just
+        // past the last line (at the end of input) points at the end of that line
+        let source = current_source();
+        let last_line = source.lines().count();
+        let (line, column) = match self.source {
+            None if self.line > last_line && last_line > 0 => {
+                (last_line, source.lines().last().map_or(0, |text| text.chars().count()) + 1)
+            }
+            _ => (self.line, self.column),
+        };
+
+        eprintln!("  {} {}:{}:{}", paint("-->", "38,139,235"), self.file, line, column);
         eprintln!("   |");
 
-        if let Some(source_line) = &self.source {
-            eprintln!("{:>3} | {}", self.line, source_line);
-            let arrow_line = format!("{:>3} | {:>width$}^", "", "", width = self.column - 1);
-            if let Some(label) = &self.label {
-                eprintln!("   | {} {}", &arrow_line[6..], label);
-            } else {
-                eprintln!("   | {}", &arrow_line[6..]);
+        let source_line = self.source.as_deref().map(str::to_string).or_else(|| match line {
+            0 => None,
+            line => source.lines().nth(line - 1).map(str::to_string),
+        });
+
+        match source_line {
+            Some(source_line) => {
+                eprintln!("{:>3} | {}", line, source_line);
+                let caret = format!("{}{}", " ".repeat(column.saturating_sub(1)), paint("^", "255,71,71"));
+                match &self.label {
+                    Some(label) => eprintln!("   | {} {}", caret, label),
+                    None => eprintln!("   | {}", caret),
+                }
             }
-        } else {
-            eprintln!("   | (source unavailable)");
+            None => eprintln!("   | (source unavailable)"),
         }
     }
 }
\ No newline at end of file
@@ -1,11 +1,15 @@
+use std::fmt;
 use std::str::FromStr;
 use crate::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub line: usize,
+    pub column: usize, // 1-based, in characters; 0 for synthesized tokens
+    pub start: usize,  // byte span in the source
+    pub end: usize,
 }
 
 impl Token {
@@ -14,6 +18,9 @@ impl Token {
             token_type,
             lexeme,
             line,
+            column: 0,
+            start: 0,
+            end: 0,
         }
     }
 }
@@ -24,6 +31,20 @@ impl Default for Token {
             token_type: TokenType::Eof, // Set default token type to EOF
             lexeme: String::new(),      // The default lexeme is an empty string
             line: 0,                    // Default line number is 0
+            column: 0,
+            start: 0,
+            end: 0,
+        }
+    }
+}
+
+// A token and where it starts, e.g. `identifier 'x' at 3:5`
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.column == 0 {
+            write!(f, "{} at line {}", self.token_type, self.line)
+        } else {
+            write!(f, "{} at {}:{}", self.token_type, self.line, self.column)
         }
     }
 }
@@ -33,18 +54,67 @@ pub struct Lexer<'a> {
     pub source: &'a str,
     pub current: usize,
     pub line: usize,
+    pub line_start: usize,   // byte offset where the current line begins
+    pub token_start: usize,  // byte offset and column of the token being scanned
+    pub token_column: usize,
     pub indent_levels: Vec<usize>,
     pub pending_indents: Vec<Token>,
+    pub bracket_depth: usize, // `(`, `[` and `{` still open; lines inside them continue
+    pub after_colon: bool,    // the last token was `:`, so a `{` opens a block
+    pub block_braces: Vec<usize>, // per open `{ ... }` block, the indentation depth at its `{`
+    pub tab_width: usize,     // columns a tab indents by; set with `set_tab_width`
 }
 
+// A tab indents as far as this many spaces unless `set_tab_width` says otherwise
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
 impl<'a> Lexer<'a> {
     pub fn new(source: &'a str) -> Lexer<'a> {
         Lexer {
             source,
             current: 0,
             line: 1,
+            line_start: 0,
+            token_start: 0,
+            token_column: 1,
             indent_levels: vec![0],
             pending_indents: Vec::new(),
+            bracket_depth: 0,
+            after_colon: false,
+            block_braces: Vec::new(),
+            tab_width: DEFAULT_TAB_WIDTH,
+        }
+    }
+
+    // Indentation is measured in spaces; a tab counts as `width` of them.
+    // Tabs and spaces in the same indentation are still an error
+    pub fn set_tab_width(&mut self, width: usize) {
+        self.tab_width = width;
+    }
+
+    // Starts over on `source`, keeping the indentation buffers' allocations
+    // and the tab width. Taking `self` lets the new source borrow for a
+    // different lifetime, so the REPL reuses one lexer for every line
+    pub fn reset<'b>(self, source: &'b str) -> Lexer<'b> {
+        let Lexer { mut indent_levels, mut pending_indents, mut block_braces, tab_width, .. } = self;
+        indent_levels.clear();
+        indent_levels.push(0);
+        pending_indents.clear();
+        block_braces.clear();
+
+        Lexer {
+            source,
+            current: 0,
+            line: 1,
+            line_start: 0,
+            token_start: 0,
+            token_column: 1,
+            indent_levels,
+            pending_indents,
+            bracket_depth: 0,
+            after_colon: false,
+            block_braces,
+            tab_width,
         }
     }
 
@@ -57,69 +127,117 @@ impl<'a> Lexer<'a> {
             return '\0';
         }
 
-        let rest = &self.source[self.current..];
-        let (ch, size) = match std::str::from_utf8(rest.as_ref()) {
-            Ok(s) => {
-                let mut chars = s.chars();
-                if let Some(c) = chars.next() {
-                    (c, c.len_utf8())
-                } else {
-                    ('\0', 1)
-                }
-            }
-            Err(_) => ('\0', 1),
-        };
-
-        self.current += size;
+        let ch = self.peek();
+        self.current += ch.len_utf8();
         ch
     }
 
     fn skip_whitespace(&mut self) {
+        let mut continued = false;
+        // Where the first line break was, which the Newline token points at
+        let mut line_end = None;
         while !self.is_at_end() {
             let c = self.peek();
             match c {
                 ' ' | '\r' | '\t' => {
                     self.advance();
                 }
+                // A `\` ending the line joins the next line onto this one
+                '\\' if self.ends_line() => {
+                    self.advance();
+                    continued = true;
+                }
                 '\n' => {
+                    let column = self.source[self.line_start..self.current].chars().count() + 1;
+                    line_end.get_or_insert((self.line, column));
                     self.line += 1;
                     self.advance();
+                    self.line_start = self.current;
+
+                    // Inside brackets or after `\` the logical line goes on,
+                    // so the next line's indentation means nothing
+                    if continued || self.bracket_depth > 0 {
+                        continued = false;
+                        line_end = None;
+                        continue;
+                    }
 
                     let mut space_count = 0;
-                    while self.peek() == ' ' {
-                        self.advance();
-                        space_count += 1;
+                    let (mut tabs, mut spaces) = (false, false);
+                    while matches!(self.peek(), ' ' | '\t') {
+                        match self.advance() {
+                            '\t' => {
+                                tabs = true;
+                                space_count += self.tab_width;
+                            }
+                            _ => {
+                                spaces = true;
+                                space_count += 1;
+                            }
+                        }
+                    }
+
+                    // Blank and comment-only lines don't open or close blocks
+                    if matches!(self.peek(), '\n' | '\r' | '\0') {
+                        continue;
+                    }
+                    if self.peek() == '/' && self.peek_next() == '/' {
+                        self.skip_comment();
+                        continue;
+                    }
+
+                    // Where the line's first token starts, in characters
+                    let indent_column = self.current - self.line_start + 1;
+
+                    // Which of a tab or a space is wider depends on the editor
+                    if tabs && spaces {
+                        self.pending_indents.push(self.indentation_error(indent_column, "Indentation mixes tabs and spaces"));
                     }
 
                     let current_indent = *self.indent_levels.last().unwrap_or(&0);
                     if space_count > current_indent {
                         self.indent_levels.push(space_count);
-                        self.pending_indents.push(Token::new(TokenType::Indent, "".to_string(), self.line));
+                        self.pending_indents.push(Token { column: indent_column, ..Token::new(TokenType::Indent, "".to_string(), self.line) });
                     } else if space_count < current_indent {
-                        while let Some(&last) = self.indent_levels.last() {
-                            if last > space_count {
-                                self.indent_levels.pop();
-                                self.pending_indents.push(Token::new(TokenType::Dedent, "".to_string(), self.line));
-                            } else {
-                                break;
-                            }
+                        // Dedenting must return to an enclosing block's level,
+                        // except inside `{ ... }` where indentation is free
+                        let kept = self.indent_levels.iter().rposition(|&level| level <= space_count).map_or(1, |i| i + 1);
+                        if self.indent_levels[kept - 1] != space_count && self.block_braces.is_empty() {
+                            let levels: Vec<String> = self.indent_levels.iter().map(usize::to_string).collect();
+                            self.pending_indents.push(self.indentation_error(indent_column, format!(
+                                "Unindent to {} spaces does not match any enclosing block (indented {})",
+                                space_count, levels.join(", "))));
+                        }
+                        for _ in kept..self.indent_levels.len() {
+                            self.pending_indents.push(Token { column: indent_column, ..Token::new(TokenType::Dedent, "".to_string(), self.line) });
                         }
+                        self.indent_levels.truncate(kept);
                     }
+
+                    // Pushed last so it comes out before any Indent or Dedent
+                    let (line, column) = line_end.take().unwrap_or((self.line, 1));
+                    self.pending_indents.push(Token { column, ..Token::new(TokenType::Newline, "".to_string(), line) });
                 }
                 _ => break,
             }
         }
     }
 
+    // Whether only whitespace follows the current char before the line ends
+    fn ends_line(&self) -> bool {
+        self.source[self.current + 1..]
+            .chars()
+            .take_while(|c| *c != '\n')
+            .all(|c| matches!(c, ' ' | '\t' | '\r'))
+    }
+
+    // O(1): ASCII is read straight from the bytes, anything else decodes
+    // only the one char at `current` (which is always on a char boundary)
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            let rest = &self.source[self.current..];
-            match std::str::from_utf8(rest.as_ref()) {
-                Ok(s) => s.chars().next().unwrap_or('\0'),
-                Err(_) => '\0',
-            }
+        match self.source.as_bytes().get(self.current) {
+            None => '\0',
+            Some(&byte) if byte.is_ascii() => byte as char,
+            Some(_) => self.source[self.current..].chars().next().unwrap_or('\0'),
         }
     }
 
@@ -135,7 +253,15 @@ impl<'a> Lexer<'a> {
     }
 
     pub fn tokenize(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
+        // Roughly one token per four bytes of source, to avoid regrowing
+        let mut tokens = Vec::with_capacity(self.source.len() / 4);
+        self.tokenize_into(&mut tokens);
+        tokens
+    }
+
+    // Replaces the contents of `tokens`, reusing its allocation across files
+    pub fn tokenize_into(&mut self, tokens: &mut Vec<Token>) {
+        tokens.clear();
 
         loop {
             if let Some(token) = self.pending_indents.pop() {
@@ -156,8 +282,6 @@ impl<'a> Lexer<'a> {
 
             tokens.push(token);
         }
-
-        tokens
     }
 
     fn skip_comment(&mut self) {
@@ -166,32 +290,37 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn skip_multiline_comment(&mut self) {
+    // Returns false when the source ends before the closing `*/`
+    fn skip_multiline_comment(&mut self) -> bool {
         while !self.is_at_end() {
             if self.peek() == '*' && self.peek_next() == '/' {
                 self.advance();
                 self.advance();
-                break;
+                return true;
             }
 
             if self.peek() == '\n' {
                 self.line += 1;
+                self.advance();
+                self.line_start = self.current;
+                continue;
             }
 
             self.advance();
         }
 
-        if self.is_at_end() {
-            panic!("Unterminated block comment");
-        }
+        false
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            '\0'
-        } else {
-            self.source.chars().nth(self.current + 1).unwrap_or('\0')
+        if self.is_at_end() {
+            return '\0';
         }
+
+        // `current` is a byte offset, so step over the whole current char
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
     /*
@@ -214,6 +343,29 @@ impl<'a> Lexer<'a> {
      */
 
     pub fn next_token(&mut self) -> Token {
+        let token = self.scan_token();
+        match token.token_type {
+            // `: {` outside brackets opens a block rather than a map, and
+            // its lines still end statements. Indentation inside the braces
+            // is not checked against the `}`, so the indentation levels
+            // opened inside are dropped when it closes
+            TokenType::Lbrace if self.after_colon && self.bracket_depth == 0 => {
+                self.block_braces.push(self.indent_levels.len());
+            }
+            TokenType::Rbrace if self.bracket_depth == 0 && !self.block_braces.is_empty() => {
+                if let Some(depth) = self.block_braces.pop() {
+                    self.indent_levels.truncate(depth);
+                }
+            }
+            TokenType::Lparen | TokenType::Lbrack | TokenType::Lbrace => self.bracket_depth += 1,
+            TokenType::Rparen | TokenType::Rbrack | TokenType::Rbrace => self.bracket_depth = self.bracket_depth.saturating_sub(1),
+            _ => {}
+        }
+        self.after_colon = token.token_type == TokenType::Colon;
+        token
+    }
+
+    fn scan_token(&mut self) -> Token {
         if let Some(token) = self.pending_indents.pop() {
             return token;
         }
@@ -224,340 +376,266 @@ impl<'a> Lexer<'a> {
             return token;
         }
 
+        // Count on from the previous token when it is on the same line, so a
+        // long line isn't rescanned from its start for every token
+        self.token_column = if self.token_start >= self.line_start {
+            self.token_column + self.source[self.token_start..self.current].chars().count()
+        } else {
+            self.source[self.line_start..self.current].chars().count() + 1
+        };
+        self.token_start = self.current;
+
         if self.is_at_end() {
-            return Token {
-                token_type: TokenType::Eof,
-                lexeme: String::new(),
-                line: self.line,
-            };
+            return self.make_token(TokenType::Eof, String::new());
         }
 
         let c = self.advance();
 
         match c {
             '+' => {
-                Token {
-                    token_type: TokenType::Plus,
-                    lexeme: "+".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Plus, "+".to_string())
             },
             '-' => {
-                Token {
-                    token_type: TokenType::Minus,
-                    lexeme: "-".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Minus, "-".to_string())
             },
             '*' => {
-                Token {
-                    token_type: TokenType::Star,
-                    lexeme: "*".to_string(),
-                    line: self.line,
+                if self.match_next('*') {
+                    self.make_token(TokenType::StarStar, "**".to_string())
+                } else {
+                    self.make_token(TokenType::Star, "*".to_string())
                 }
             } ,
             '.' => {
-                Token {
-                    token_type: TokenType::Dot,
-                    lexeme: ".".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Dot, ".".to_string())
             },
             '/' => {
                 if self.match_next('/') {
                     self.skip_comment();
                     self.next_token()
                 } else if self.match_next('*') {
-                    self.skip_multiline_comment();
-                    self.next_token()
-                } else {
-                    Token {
-                        token_type: TokenType::Div,
-                        lexeme: "/".to_string(),
-                        line: self.line,
+                    let start_line = self.line;
+                    if self.skip_multiline_comment() {
+                        self.next_token()
+                    } else {
+                        Token {
+                            line: start_line,
+                            ..self.error_token("Unterminated block comment")
+                        }
                     }
+                } else {
+                    self.make_token(TokenType::Div, "/".to_string())
                 }
             },
             ';' => {
-                Token {
-                    token_type: TokenType::SemiColon,
-                    lexeme: ";".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::SemiColon, ";".to_string())
             },
             ':' => {
-                Token {
-                    token_type: TokenType::Colon,
-                    lexeme: ":".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Colon, ":".to_string())
             },
             '<' => {
                 if self.match_next('=') {
-                    Token {
-                        token_type: TokenType::LchevrEq,
-                        lexeme: "<=".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::LchevrEq, "<=".to_string())
                 } else {
-                    Token {
-                        token_type: TokenType::Lchevr,
-                        lexeme: "<".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::Lchevr, "<".to_string())
                 }
 
             },
             '>' => {
                 if self.match_next('=') {
-                    Token {
-                        token_type: TokenType::RchevrEq,
-                        lexeme: ">=".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::RchevrEq, ">=".to_string())
                 } else {
-                    Token {
-                        token_type: TokenType::Rchevr,
-                        lexeme: ">".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::Rchevr, ">".to_string())
                 }
 
             },
             '(' => {
-                Token {
-                    token_type: TokenType::Lparen,
-                    lexeme: "(".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Lparen, "(".to_string())
             },
             ')' => {
-                Token {
-                    token_type: TokenType::Rparen,
-                    lexeme: ")".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Rparen, ")".to_string())
             },
             '[' => {
-                Token {
-                    token_type: TokenType::Lbrack,
-                    lexeme: "[".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Lbrack, "[".to_string())
             },
             ']' => {
-                Token {
-                    token_type: TokenType::Rbrack,
-                    lexeme: "]".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Rbrack, "]".to_string())
+            },
+            '{' => {
+                self.make_token(TokenType::Lbrace, "{".to_string())
+            },
+            '}' => {
+                self.make_token(TokenType::Rbrace, "}".to_string())
             },
             '=' => {
                 if self.match_next('=') {
-                    Token {
-                        token_type: TokenType::EqualTwo,
-                        lexeme: "==".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::EqualTwo, "==".to_string())
                 } else {
-                    Token {
-                        token_type: TokenType::Equal,
-                        lexeme: "=".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::Equal, "=".to_string())
                 }
             },
             '&' => {
                 if self.match_next('&') {
-                    Token {
-                        token_type: TokenType::LogicalAnd,
-                        lexeme: "&&".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::LogicalAnd, "&&".to_string())
                 } else {
-                    panic!("Error");
+                    self.error_token("Unexpected character '&', did you mean '&&'?")
                 }
             },
             '|' => {
                 if self.match_next('|') {
-                    Token {
-                        token_type: TokenType::LogicalOr,
-                        lexeme: "||".to_string(),
-                        line: self.line,
+                    if self.match_next('=') {
+                        self.make_token(TokenType::LogicalOrAssign, "||=".to_string())
+                    } else {
+                        self.make_token(TokenType::LogicalOr, "||".to_string())
                     }
                 } else {
-                    panic!("Error");
+                    self.error_token("Unexpected character '|', did you mean '||'?")
+                }
+            },
+            '?' => {
+                if self.match_next('?') && self.match_next('=') {
+                    self.make_token(TokenType::NullCoalesceAssign, "??=".to_string())
+                } else {
+                    self.error_token("Unexpected character '?', did you mean '??='?")
                 }
             },
             '!' => {
                 if self.match_next('=') {
-                    Token {
-                        token_type: TokenType::NotEqual,
-                        lexeme: "!=".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::NotEqual, "!=".to_string())
                 } else {
-                    Token {
-                        token_type: TokenType::Not,
-                        lexeme: "!".to_string(),
-                        line: self.line,
-                    }
+                    self.make_token(TokenType::Not, "!".to_string())
                 }
             },
+            // Floor division is spelled `~/` because `//` always starts a
+            // comment, even after a value as in `x = 1 // one`
+            '~' if self.peek() == '/' => {
+                self.advance();
+                self.make_token(TokenType::FloorDiv, "~/".to_string())
+            },
             '^' => {
-                Token {
-                    token_type: TokenType::Xor,
-                    lexeme: "^".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Xor, "^".to_string())
+            },
+            '%' => {
+                self.make_token(TokenType::Percent, "%".to_string())
             },
             ',' => {
-                Token {
-                    token_type: TokenType::Comma,
-                    lexeme: ",".to_string(),
-                    line: self.line,
-                }
+                self.make_token(TokenType::Comma, ",".to_string())
             },
+            // A string may span lines; its token and any error point at the
+            // opening quote
             '"' => {
-                let string_value = self.string();
-                Token {
-                    token_type: TokenType::String(string_value.clone()),
-                    lexeme: format!("\"{}\"", string_value),
-                    line: self.line,
-                }
+                let start_line = self.line;
+                let token = match self.string() {
+                    Ok(mut parts) => {
+                        let lexeme = self.source[self.token_start..self.current].to_string();
+                        match parts.as_slice() {
+                            [] => self.make_token(TokenType::String(String::new()), lexeme),
+                            [StringPart::Text(_)] => match parts.pop() {
+                                Some(StringPart::Text(text)) => self.make_token(TokenType::String(text), lexeme),
+                                _ => unreachable!(),
+                            },
+                            _ => self.make_token(TokenType::InterpolatedString(parts), lexeme),
+                        }
+                    }
+                    Err(message) => self.error_token(message),
+                };
+                Token { line: start_line, ..token }
             },
-            'a'..='z' | 'A'..='Z' => {
+            // Any Unicode letter may start a name, e.g. `café` or `π`
+            c if c.is_alphabetic() || c == '_' => {
                 let identifier = self.identifier();
                 match identifier.as_str() {
                     "fun" => {
-                        Token {
-                            token_type: TokenType::Fun,
-                            lexeme: "fun".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Fun, "fun".to_string())
+                    },
+                    "var" | "let" => {
+                        self.make_token(TokenType::Var, identifier)
+                    },
+                    "const" => {
+                        self.make_token(TokenType::Const, "const".to_string())
                     },
                     "if" => {
-                        Token {
-                            token_type: TokenType::If,
-                            lexeme: "if".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::If, "if".to_string())
                     },
                     "else" => {
-                        Token {
-                            token_type: TokenType::Else,
-                            lexeme: "else".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Else, "else".to_string())
+                    },
+                    "elif" => {
+                        self.make_token(TokenType::Elif, "elif".to_string())
                     },
                     "while" => {
-                        Token {
-                            token_type: TokenType::While,
-                            lexeme: "while".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::While, "while".to_string())
                     },
                     "for" => {
-                        Token {
-                            token_type: TokenType::For,
-                            lexeme: "for".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::For, "for".to_string())
+                    },
+                    "match" => {
+                        self.make_token(TokenType::Match, "match".to_string())
+                    },
+                    "case" => {
+                        self.make_token(TokenType::Case, "case".to_string())
+                    },
+                    "default" => {
+                        self.make_token(TokenType::Default, "default".to_string())
+                    },
+                    "in" => {
+                        self.make_token(TokenType::In, "in".to_string())
                     },
                     "import" => {
-                        Token {
-                            token_type: TokenType::Import,
-                            lexeme: "import".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Import, "import".to_string())
                     },
                     "return" => {
-                        Token {
-                            token_type: TokenType::Return,
-                            lexeme: "return".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Return, "return".to_string())
                     },
                     "continue" => {
-                        Token {
-                            token_type: TokenType::Continue,
-                            lexeme: "continue".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Continue, "continue".to_string())
                     },
                     "print" => {
-                        Token {
-                            token_type: TokenType::Print,
-                            lexeme: "print".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Print, "print".to_string())
                     },
                     "input" => {
-                        Token {
-                            token_type: TokenType::Input,
-                            lexeme: "input".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Input, "input".to_string())
                     },
                     "println" => {
-                        Token {
-                            token_type: TokenType::Println,
-                            lexeme: "println".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Println, "println".to_string())
                     },
                     "break" => {
-                        Token {
-                            token_type: TokenType::Break,
-                            lexeme: "break".to_string(),
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Break, "break".to_string())
+                    },
+                    // Word spellings of `&&`, `||` and `!`; the lexeme keeps the word
+                    "and" => {
+                        self.make_token(TokenType::LogicalAnd, identifier)
+                    },
+                    "or" => {
+                        self.make_token(TokenType::LogicalOr, identifier)
+                    },
+                    "not" => {
+                        self.make_token(TokenType::Not, identifier)
+                    },
+                    "none" => {
+                        self.make_token(TokenType::None, "none".to_string())
+                    },
+                    "true" => {
+                        self.make_token(TokenType::Bool(true), "true".to_string())
+                    },
+                    "false" => {
+                        self.make_token(TokenType::Bool(false), "false".to_string())
                     },
                     _ => {
-                        Token {
-                            token_type: TokenType::Identifier(identifier.clone()),
-                            lexeme: identifier,
-                            line: self.line,
-                        }
+                        self.make_token(TokenType::Identifier(identifier.clone()), identifier)
                     }
                 }
             },
             '0'..='9' => {
-                let mut num_str = self.number().to_string(); // Converting Numbers to Strings
-                if self.peek() == '.' { // If the following characters are dots, handle mistakes
-                    num_str.push('.'); // Add a dot
-                    self.advance(); // turning over a mole
-                    // deal with numbers that can follow a mistake
-                    while self.peek().is_digit(10) {
-                        num_str.push(self.advance()); // Keep adding numbers
-                    }
-                }
-
-                // Safe handling of errors in accidental parsing
-                let token_type = match num_str.parse::<f64>() {
-                    Ok(n) => {
-                        if n.fract() == 0.0 {
-                            TokenType::Number(n as i64)
-                        } else {
-                            TokenType::Float(n)
-                        }
-                    }
-                    Err(_) => TokenType::Float(0.0),
-                };
-
-                Token {
-                    token_type,
-                    lexeme: num_str, // Save real string to lexeme
-                    line: self.line,
+                match self.number() {
+                    Ok((token_type, lexeme)) => self.make_token(token_type, lexeme),
+                    Err(message) => self.error_token(message),
                 }
             },
             _ => {
                 if c == '\0' {
-                    eprintln!("[eprintln] Null character encountered — likely unintended");
-                    panic!("[panic] Null character (`\\0`) is not allowed in source");
+                    self.error_token("Null character (`\\0`) is not allowed in source")
                 } else {
-                    eprintln!("[eprintln] Unexpected character: {:?} (code: {})", c, c as u32);
-                    panic!("[panic] Unexpected character: {:?}", c);
+                    self.error_token(format!("Unexpected character {:?} (code: {})", c, c as u32))
                 }
             }
         }
@@ -591,32 +669,117 @@ impl<'a> Lexer<'a> {
      */
 
     // Add string literal processing function
-    fn string(&mut self) -> String {
-        if self.peek() == '"' {
-            self.advance();
-        }
-
+    // The opening quote is already consumed, so `""` ends at the next char.
+    // Each `${...}` is lexed on its own and kept as a `StringPart::Code`;
+    // `\$` is a literal `$`, so `\${` does not interpolate
+    fn string(&mut self) -> Result<Vec<StringPart>, String> {
+        let mut parts = Vec::new();
         let mut string_literal = String::new();
 
         while !self.is_at_end() && self.peek() != '"' {
-            string_literal.push(self.advance());
+            let c = self.advance();
+            if c == '$' && self.peek() == '{' {
+                self.advance(); // '{'
+                if !string_literal.is_empty() {
+                    parts.push(StringPart::Text(std::mem::take(&mut string_literal)));
+                }
+                parts.push(StringPart::Code(self.interpolation()?));
+                continue;
+            }
+            if c == '\n' {
+                self.line += 1;
+                self.line_start = self.current;
+            }
+            if c != '\\' {
+                string_literal.push(c);
+                continue;
+            }
+
+            let escaped = match self.advance() {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '"' => '"',
+                '\\' => '\\',
+                '$' => '$',
+                other => return Err(format!("Unknown escape sequence '\\{}' in string", other)),
+            };
+            string_literal.push(escaped);
         }
 
         if self.is_at_end() {
-            panic!("Unterminated string.");
+            return Err("Unterminated string".to_string());
         }
 
         self.advance(); // closing quote
 
-        string_literal
+        if !string_literal.is_empty() {
+            parts.push(StringPart::Text(string_literal));
+        }
+        Ok(parts)
     }
 
+    // The tokens between `${` and its matching `}`, positioned where they
+    // are in the source. Strings nested in the expression may hold braces
+    fn interpolation(&mut self) -> Result<Vec<Token>, String> {
+        let start = self.current;
+        let column = self.source[self.line_start..start].chars().count() + 1;
+        let mut depth = 0;
+        loop {
+            match self.advance() {
+                '\0' | '\n' => return Err("Unterminated '${' in string".to_string()),
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                '"' => {
+                    while !matches!(self.peek(), '"' | '\n' | '\0') {
+                        if self.advance() == '\\' {
+                            self.advance();
+                        }
+                    }
+                    if self.peek() == '"' {
+                        self.advance();
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let code = &self.source[start..self.current - 1];
+        let mut inner = Lexer::new(code);
+        inner.set_tab_width(self.tab_width);
+        let mut tokens = Vec::new();
+        for token in inner.tokenize() {
+            match token.token_type {
+                TokenType::Error(message) | TokenType::IndentationError(message) => return Err(message),
+                TokenType::Newline | TokenType::Indent | TokenType::Dedent | TokenType::Eof => {}
+                _ => tokens.push(Token {
+                    line: self.line,
+                    column: token.column + column - 1,
+                    start: token.start + start,
+                    end: token.end + start,
+                    ..token
+                }),
+            }
+        }
+
+        if tokens.is_empty() {
+            return Err("Empty '${}' in string".to_string());
+        }
+        tokens.push(Token {
+            line: self.line,
+            column: column + code.chars().count(),
+            start: self.current - 1,
+            end: self.current,
+            ..Token::new(TokenType::Rbrace, "}".to_string(), self.line)
+        });
+        Ok(tokens)
+    }
+
+    // Both scanners slice from `token_start`, the byte offset recorded before
+    // the first char was consumed, rather than assuming it was one byte wide
     fn identifier(&mut self) -> String {
-        let start = if self.current > 0 {
-            self.current - 1
-        } else {
-            0
-        };
+        let start = self.token_start;
 
         while !self.is_at_end() {
             let c = self.peek();
@@ -630,13 +793,111 @@ impl<'a> Lexer<'a> {
         self.source[start..self.current].to_string()
     }
 
-    fn number(&mut self) -> i64 {
-        let start = self.current - 1;
-        while !self.is_at_end() && self.peek().is_numeric() {
+    // Digits with optional `_` separators, a fraction and an exponent part
+    fn number(&mut self) -> Result<(TokenType, String), String> {
+        let start = self.token_start;
+        self.digits();
+
+        let mut has_fraction = false;
+        if self.peek() == '.' {
+            has_fraction = true;
+            self.advance(); // consume '.'
+            self.digits();
+        }
+
+        let mut has_exponent = false;
+        if matches!(self.peek(), 'e' | 'E')
+            && (self.peek_next().is_ascii_digit() || matches!(self.peek_next(), '+' | '-'))
+        {
+            has_exponent = true;
+            self.advance(); // consume 'e'
+            if matches!(self.peek(), '+' | '-') {
+                self.advance();
+            }
+            if !self.peek().is_ascii_digit() {
+                return Err(format!("Expected digits in the exponent of '{}'", &self.source[start..self.current]));
+            }
+            self.digits();
+        }
+
+        let lexeme = self.source[start..self.current].to_string();
+
+        // `_` must sit between two digits: no `1__0`, `1_`, `1_.5` or `1e_5`
+        let chars: Vec<char> = lexeme.chars().collect();
+        for (i, c) in chars.iter().enumerate() {
+            if *c == '_' {
+                let between_digits = i > 0
+                    && chars[i - 1].is_ascii_digit()
+                    && chars.get(i + 1).is_some_and(|next| next.is_ascii_digit());
+                if !between_digits {
+                    return Err(format!("Misplaced '_' separator in number '{}'", lexeme));
+                }
+            }
+        }
+
+        let digits: String = lexeme.chars().filter(|c| *c != '_').collect();
+
+        // An `i` or `f` suffix forces the representation: `5i` is a Number, `5f` a Float
+        let suffix_start = self.current;
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+        let suffix = &self.source[suffix_start..self.current];
+        let lexeme = format!("{}{}", lexeme, suffix);
+
+        let is_float = match suffix {
+            "" => has_fraction || has_exponent,
+            "f" => true,
+            "i" if has_fraction || has_exponent => {
+                return Err(format!("'{}' has a fractional part and cannot take the 'i' suffix", lexeme));
+            }
+            "i" => false,
+            _ => return Err(format!("Invalid suffix '{}' on number '{}', expected 'i' or 'f'", suffix, lexeme)),
+        };
+
+        // Too large to represent is an error, not a silent zero or infinity
+        let token_type = if is_float {
+            match digits.parse::<f64>() {
+                Ok(value) if value.is_finite() => TokenType::Float(value),
+                _ => return Err(format!("Float literal '{}' out of range", lexeme)),
+            }
+        } else {
+            match i64::from_str(&digits) {
+                Ok(value) => TokenType::Number(value),
+                Err(_) => return Err(format!("Integer literal '{}' out of range (at most {})", lexeme, i64::MAX)),
+            }
+        };
+
+        Ok((token_type, lexeme))
+    }
+
+    fn digits(&mut self) {
+        while !self.is_at_end() && (self.peek().is_ascii_digit() || self.peek() == '_') {
             self.advance();
         }
+    }
+
+    fn make_token(&self, token_type: TokenType, lexeme: String) -> Token {
+        Token {
+            token_type,
+            lexeme,
+            line: self.line,
+            column: self.token_column,
+            start: self.token_start,
+            end: self.current,
+        }
+    }
+
+    // Lexing errors become tokens so the parser can report them with a position
+    fn indentation_error(&self, column: usize, message: impl Into<String>) -> Token {
+        Token {
+            column,
+            ..Token::new(TokenType::IndentationError(message.into()), "".to_string(), self.line)
+        }
+    }
 
-        let number_str = &self.source[start..self.current];
-        i64::from_str(number_str).unwrap_or_else(|_| 0)
+    fn error_token(&self, message: impl Into<String>) -> Token {
+        let message = message.into();
+        self.make_token(TokenType::Error(message.clone()), message)
     }
 }
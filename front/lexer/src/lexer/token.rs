@@ -1,13 +1,24 @@
+use std::fmt;
+use crate::Token;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenType {
     Indent,
     Dedent,
+    Newline,       // end of a logical line, which ends a simple statement
 
     Fun,
+    Var,           // var or let
+    Const,
     If,
     Else,
+    Elif,
     While,
     For,
+    In,
+    Match,
+    Case,
+    Default,
     Import,
     Return,
     Continue,
@@ -16,23 +27,31 @@ pub enum TokenType {
     Print,
     Println,
 
-    LogicalAnd,    // &&
-    LogicalOr,     // ||
+    LogicalAnd,    // && or and
+    LogicalOr,     // || or or
+    LogicalOrAssign,    // ||=
+    NullCoalesceAssign, // ??=
     NotEqual,      // !=
-    Not,           // !
+    Not,           // ! or not
     Xor,           // ^
 
     Operator(String),
 
     Identifier(String),
     String(String),
+    InterpolatedString(Vec<StringPart>), // a string with at least one `${...}`
     Number(i64),
     Float(f64),
+    Bool(bool),
+    None,          // none
 
     Plus,          // +
     Minus,         // -
     Star,          // *
+    StarStar,      // **
     Div,           // /
+    FloorDiv,      // ~/
+    Percent,       // %
     Equal,         // =
     EqualTwo,      // ==
     Comma,         // ,
@@ -47,8 +66,95 @@ pub enum TokenType {
     Rparen,        // )
     Lbrack,        // [
     Rbrack,        // ]
+    Lbrace,        // {
+    Rbrace,        // }
 
     Eof,
-    Error,
+    Error(String), // message describing the malformed input
+    IndentationError(String), // a line indented inconsistently with the blocks around it
     Whitespace,
-}
\ No newline at end of file
+}
+
+// A piece of an interpolated string: literal text, or the tokens of one
+// `${...}` after the `${`, ending with its closing `}`
+#[derive(Debug, PartialEq, Clone)]
+pub enum StringPart {
+    Text(String),
+    Code(Vec<Token>),
+}
+
+// How a token is named to users, e.g. in "found ..." parse errors
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            TokenType::Indent => return write!(f, "indentation"),
+            TokenType::Dedent => return write!(f, "end of block"),
+            TokenType::Newline => return write!(f, "end of line"),
+            TokenType::Eof => return write!(f, "end of input"),
+            TokenType::Whitespace => return write!(f, "whitespace"),
+            TokenType::Error(message) => return write!(f, "invalid input ({})", message),
+            TokenType::IndentationError(message) => return write!(f, "invalid indentation ({})", message),
+            TokenType::Identifier(name) => return write!(f, "identifier '{}'", name),
+            TokenType::String(text) => return write!(f, "string {:?}", text),
+            TokenType::InterpolatedString(_) => return write!(f, "interpolated string"),
+            TokenType::Number(n) => return write!(f, "number {}", n),
+            TokenType::Float(x) => return write!(f, "number {}", x),
+            TokenType::Operator(op) => op.as_str(),
+            TokenType::Bool(true) => "true",
+            TokenType::Bool(false) => "false",
+            TokenType::None => "none",
+
+            TokenType::Fun => "fun",
+            TokenType::Var => "var",
+            TokenType::Const => "const",
+            TokenType::If => "if",
+            TokenType::Else => "else",
+            TokenType::Elif => "elif",
+            TokenType::While => "while",
+            TokenType::For => "for",
+            TokenType::In => "in",
+            TokenType::Match => "match",
+            TokenType::Case => "case",
+            TokenType::Default => "default",
+            TokenType::Import => "import",
+            TokenType::Return => "return",
+            TokenType::Continue => "continue",
+            TokenType::Break => "break",
+            TokenType::Input => "input",
+            TokenType::Print => "print",
+            TokenType::Println => "println",
+
+            TokenType::LogicalAnd => "&&",
+            TokenType::LogicalOr => "||",
+            TokenType::LogicalOrAssign => "||=",
+            TokenType::NullCoalesceAssign => "??=",
+            TokenType::NotEqual => "!=",
+            TokenType::Not => "!",
+            TokenType::Xor => "^",
+            TokenType::Plus => "+",
+            TokenType::Minus => "-",
+            TokenType::Star => "*",
+            TokenType::StarStar => "**",
+            TokenType::Div => "/",
+            TokenType::FloorDiv => "~/",
+            TokenType::Percent => "%",
+            TokenType::Equal => "=",
+            TokenType::EqualTwo => "==",
+            TokenType::Comma => ",",
+            TokenType::Dot => ".",
+            TokenType::SemiColon => ";",
+            TokenType::Colon => ":",
+            TokenType::Lchevr => "<",
+            TokenType::LchevrEq => "<=",
+            TokenType::Rchevr => ">",
+            TokenType::RchevrEq => ">=",
+            TokenType::Lparen => "(",
+            TokenType::Rparen => ")",
+            TokenType::Lbrack => "[",
+            TokenType::Rbrack => "]",
+            TokenType::Lbrace => "{",
+            TokenType::Rbrace => "}",
+        };
+        write!(f, "'{}'", symbol)
+    }
+}
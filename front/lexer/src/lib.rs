@@ -1,4 +1,3 @@
 pub mod lexer;
 
 pub use lexer::*;
-pub use lexer::token::*;
@@ -0,0 +1,87 @@
+use lexer::{Lexer, Token, TokenType};
+
+fn tokens_of(source: &str) -> Vec<Token> {
+    Lexer::new(source).tokenize()
+}
+
+#[test]
+fn reset_lexes_like_a_fresh_lexer() {
+    let first = "fun f(x):\n    if (x > 1): {\n        return x\n    }\n";
+    let second = "print(f(2), \"two\")\nvalues = [1, 2.5]\n";
+
+    let mut lexer = Lexer::new(first);
+    assert_eq!(lexer.tokenize(), tokens_of(first));
+
+    // Indentation, brace blocks and position left over from `first` must not leak
+    let mut lexer = lexer.reset(second);
+    assert_eq!(lexer.tokenize(), tokens_of(second));
+
+    // A reset partway through a source starts from the top of the new one
+    let mut lexer = Lexer::new(first);
+    lexer.next_token();
+    lexer.next_token();
+    let mut lexer = lexer.reset(second);
+    assert_eq!(lexer.tokenize(), tokens_of(second));
+}
+
+#[test]
+fn reset_keeps_the_tab_width() {
+    let source = "if (x):\n\tprint(x)\n\tprint(x)\n";
+    let mut lexer = Lexer::new("");
+    lexer.set_tab_width(8);
+    let mut lexer = lexer.reset(source);
+
+    let mut fresh = Lexer::new(source);
+    fresh.set_tab_width(8);
+    assert_eq!(lexer.tokenize(), fresh.tokenize());
+}
+
+#[test]
+fn tokenize_into_fills_the_callers_buffer() {
+    let long = "total = 0\nfor i in range(0, 10):\n    total = total + i * 2\nprint(total)\n";
+    let short = "print(\"hi\")\n";
+
+    let mut buffer = Vec::new();
+    Lexer::new(long).tokenize_into(&mut buffer);
+    assert_eq!(buffer, tokens_of(long));
+
+    // Reusing the buffer keeps its allocation and leaves nothing from `long`
+    let capacity = buffer.capacity();
+    Lexer::new(short).tokenize_into(&mut buffer);
+    assert_eq!(buffer, tokens_of(short));
+    assert_eq!(buffer.capacity(), capacity);
+}
+
+#[test]
+fn token_types_render_by_kind() {
+    let rendered: Vec<String> = [
+        TokenType::Identifier("x".to_string()),
+        TokenType::Number(2),
+        TokenType::Float(4.5),
+        TokenType::String("two".to_string()),
+        TokenType::While,
+        TokenType::Plus,
+        TokenType::Rparen,
+        TokenType::Newline,
+        TokenType::Eof,
+    ]
+    .iter()
+    .map(ToString::to_string)
+    .collect();
+
+    assert_eq!(rendered, [
+        "identifier 'x'", "number 2", "number 4.5", "string \"two\"",
+        "'while'", "'+'", "')'", "end of line", "end of input",
+    ]);
+}
+
+#[test]
+fn tokens_render_with_their_position() {
+    let tokens = tokens_of("x = 1\n  + y");
+    assert_eq!(tokens[0].to_string(), "identifier 'x' at 1:1");
+    assert_eq!(tokens[2].to_string(), "number 1 at 1:5");
+
+    // Synthesized tokens have no column
+    let token = Token::new(TokenType::Dedent, String::new(), 3);
+    assert_eq!(token.to_string(), "end of block at line 3");
+}
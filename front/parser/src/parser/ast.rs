@@ -3,6 +3,7 @@ pub enum Value {
     Int(i64),
     Float(f64),
     Text(String),
+    Bool(bool),
 }
 
 #[derive(Debug, Clone)]
@@ -33,13 +34,26 @@ pub enum FormatPart {
     Placeholder,
 }
 
+// A piece of an interpolated string literal
+#[derive(Debug, Clone)]
+pub enum InterpolationPart {
+    Text(String),
+    Expression(Expression),
+}
+
+// `line`/`column` on the variants that can fail at runtime locate their
+// errors: the function or method name, or the operator
 #[derive(Debug, Clone)]
 pub enum Expression {
     FunctionCall {
         name: String,
         args: Vec<Expression>,
+        line: usize,
+        column: usize,
     },
     Literal(Literal),
+    // `"text ${expr} text"`, joined as print shows each value
+    Interpolation(Vec<InterpolationPart>),
     Variable(String),
     Deref(Box<Expression>),
     AddressOf(Box<Expression>),
@@ -47,8 +61,33 @@ pub enum Expression {
         left: Box<Expression>,
         operator: Operator,
         right: Box<Expression>,
+        line: usize,
+        column: usize,
     },
     Grouped(Box<Expression>),
+    ArrayLiteral(Vec<Expression>),
+    // `{ key: value, ... }`, in insertion order
+    MapLiteral(Vec<(Expression, Expression)>),
+    // `target[index]`; `line`/`column` locate the `[`
+    Index {
+        target: Box<Expression>,
+        index: Box<Expression>,
+        line: usize,
+        column: usize,
+    },
+    UnaryExpression {
+        operator: Operator,
+        operand: Box<Expression>,
+        line: usize,
+        column: usize,
+    },
+    MethodCall {
+        receiver: Box<Expression>,
+        method: String,
+        args: Vec<Expression>,
+        line: usize,
+        column: usize,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +95,8 @@ pub enum Literal {
     Number(i64),
     Float(f64),
     String(String),
+    Bool(bool),
+    None,
 }
 
 #[derive(Debug, Clone)]
@@ -64,6 +105,9 @@ pub enum Operator {
     Subtract,
     Multiply,
     Divide,
+    FloorDivide,
+    Remainder,
+    Power,
     GreaterEqual,
     LessEqual,
     Greater,
@@ -71,20 +115,64 @@ pub enum Operator {
     Equal,
     NotEqual,
     LogicalAnd,
+    Not,
     BitwiseAnd,
     LogicalOr,
     BitwiseOr,
+    Xor,
     Assign,
+    LogicalOrAssign,
+    NullCoalesceAssign,
+}
+
+impl Operator {
+    // As written in source, for diagnostics
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::FloorDivide => "~/",
+            Operator::Remainder => "%",
+            Operator::Power => "**",
+            Operator::GreaterEqual => ">=",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::Less => "<",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::LogicalAnd => "&&",
+            Operator::BitwiseAnd => "&",
+            Operator::LogicalOr => "||",
+            Operator::BitwiseOr => "|",
+            Operator::Xor => "^",
+            Operator::Not => "!",
+            Operator::Assign => "=",
+            Operator::LogicalOrAssign => "||=",
+            Operator::NullCoalesceAssign => "??=",
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        matches!(self, Operator::Equal | Operator::NotEqual | Operator::Less | Operator::Greater
+            | Operator::LessEqual | Operator::GreaterEqual)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum StatementNode {
-    PrintArgs(Vec<Expression>),
+    // Writes `args` joined by `sep` (default " "), followed by `end`
+    // (default a newline)
+    PrintArgs {
+        args: Vec<Expression>,
+        sep: Option<Expression>,
+        end: Option<Expression>,
+    },
+    // `if`, then each `else if`/`elif`, in order; the first true condition wins
     If {
-        condition: Expression,
-        body: Vec<ASTNode>,
-        else_if_blocks: Option<Box<Vec<ASTNode>>>,
-        else_block: Option<Box<Vec<ASTNode>>>,
+        branches: Vec<(Expression, Vec<ASTNode>)>,
+        else_block: Option<Vec<ASTNode>>,
     },
     For {
         initialization: Expression,
@@ -92,13 +180,68 @@ pub enum StatementNode {
         increment: Expression,
         body: Vec<ASTNode>,
     },
+    // `for var in iterable:` runs `body` once per element; `line`/`column`
+    // locate the iterable
+    ForIn {
+        var: String,
+        iterable: Expression,
+        body: Vec<ASTNode>,
+        line: usize,
+        column: usize,
+    },
+    // Runs the first arm whose value equals `scrutinee`, else `default`
+    Match {
+        scrutinee: Expression,
+        arms: Vec<(Expression, Vec<ASTNode>)>,
+        default: Option<Vec<ASTNode>>,
+    },
+    // `else_block` runs when the loop ends without `break`
     While {
         condition: Expression,
         body: Vec<ASTNode>,
+        else_block: Option<Vec<ASTNode>>,
+    },
+    WhileLet {
+        variable: String,
+        value: Expression,
+        body: Vec<ASTNode>,
     },
+    // `line`/`column` locate the target name, e.g. for assigning a const
     Assign {
         variable: String,
         value: Expression,
+        line: usize,
+        column: usize,
+    },
+    // `variable[index] = value` replaces an array element or sets a map key
+    IndexAssign {
+        variable: String,
+        index: Expression,
+        value: Expression,
+        line: usize,
+        column: usize,
+    },
+    // `a, b = 1, 2`: every value is evaluated before any target is bound,
+    // so `a, b = b, a` swaps; `line`/`column` locate the first target
+    MultiAssign {
+        targets: Vec<String>,
+        values: Vec<Expression>,
+        line: usize,
+        column: usize,
+    },
+    ConditionalAssign {
+        variable: String,
+        operator: Operator,
+        value: Expression,
+        line: usize,
+        column: usize,
+    },
+    // `line`/`column` locate the module path
+    Import {
+        path: String,
+        once: bool,
+        line: usize,
+        column: usize,
     },
     Break,
     Continue,
@@ -111,6 +254,7 @@ pub enum Mutability {
     Var,
     Let,
     LetMut,
+    Const,
 }
 
 #[derive(Debug, Clone)]
@@ -1,27 +1,31 @@
 use std::iter::Peekable;
 use std::slice::Iter;
-use lexer::{Token, TokenType};
-use crate::ast::{Operator, Expression, FormatPart, Literal};
+use lexer::{StringPart, Token, TokenType};
+use crate::ast::{Operator, Expression, FormatPart, InterpolationPart, Literal};
+use error::LoaErrorKind;
+use crate::parser::{describe, parse_function_call, report_at};
 
+// Splits "a {} b" into literal text and `{}` placeholders; `{{` and `}}`
+// stand for a literal `{` and `}`
 pub fn parse_format_string(s: &str) -> Vec<FormatPart> {
     let mut parts = Vec::new();
     let mut buffer = String::new();
     let mut chars = s.chars().peekable();
 
     while let Some(c) = chars.next() {
-        if c == '{' {
-            if let Some('}') = chars.peek() {
+        match (c, chars.peek()) {
+            ('{', Some('}')) => {
                 chars.next();
                 if !buffer.is_empty() {
-                    parts.push(FormatPart::Literal(buffer.clone()));
-                    buffer.clear();
+                    parts.push(FormatPart::Literal(std::mem::take(&mut buffer)));
                 }
                 parts.push(FormatPart::Placeholder);
-            } else {
+            }
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
                 buffer.push(c);
             }
-        } else {
-            buffer.push(c);
+            _ => buffer.push(c),
         }
     }
 
@@ -32,144 +36,171 @@ pub fn parse_format_string(s: &str) -> Vec<FormatPart> {
     parts
 }
 
+// Binary operators by precedence, loosest first; all are left-associative
+// except `**`. Unary `-` sits between `*` and `**`, so `-2 ** 2` is -(2 ** 2).
+// The `not` keyword sits just above `&&`, so `not a == b` is !(a == b) and
+// `not a && b` is (not a) && b, as in Python; `!` binds like unary `-`, so
+// `!a == b` is (!a) == b.
+//
+//   1  ||  or
+//   2  &&  and
+//   -  not
+//   3  ==  !=  <  >  <=  >=
+//   4  ^    (as in Python, so `6 ^ 3 == 5` compares the XOR result)
+//   5  +  -
+//   6  *  /  ~/  %
+//   -  unary -  !
+//   7  **
+const UNARY_OPERAND: u8 = 7;
+const NOT_OPERAND: u8 = 3;
+
+fn binary_operator(token_type: &TokenType) -> Option<(Operator, u8)> {
+    let operator = match token_type {
+        TokenType::LogicalOr => (Operator::LogicalOr, 1),
+        TokenType::LogicalAnd => (Operator::LogicalAnd, 2),
+        TokenType::EqualTwo => (Operator::Equal, 3),
+        TokenType::NotEqual => (Operator::NotEqual, 3),
+        TokenType::Lchevr => (Operator::Less, 3),
+        TokenType::Rchevr => (Operator::Greater, 3),
+        TokenType::LchevrEq => (Operator::LessEqual, 3),
+        TokenType::RchevrEq => (Operator::GreaterEqual, 3),
+        TokenType::Xor => (Operator::Xor, 4),
+        TokenType::Plus => (Operator::Add, 5),
+        TokenType::Minus => (Operator::Subtract, 5),
+        TokenType::Star => (Operator::Multiply, 6),
+        TokenType::Div => (Operator::Divide, 6),
+        TokenType::FloorDiv => (Operator::FloorDivide, 6),
+        TokenType::Percent => (Operator::Remainder, 6),
+        TokenType::StarStar => (Operator::Power, 7),
+        _ => return None,
+    };
+    Some(operator)
+}
+
 pub fn parse_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
 where
     T: Iterator<Item = &'a Token>,
 {
-    let expr = parse_logical_expression(tokens)?;
-    Some(expr)
+    parse_binary_expression(tokens, 0)
 }
 
-pub fn parse_logical_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
+// Precedence climbing: only operators binding at least as tightly as
+// `min_precedence` are folded into this subexpression
+fn parse_binary_expression<'a, T>(tokens: &mut Peekable<T>, min_precedence: u8) -> Option<Expression>
 where
     T: Iterator<Item = &'a Token>,
 {
-    let mut left = parse_relational_expression(tokens)?;
-
-    while let Some(token) = tokens.peek() {
-        match token.token_type {
-            TokenType::LogicalAnd | TokenType::LogicalOr => {
-                let op = match token.token_type {
-                    TokenType::LogicalAnd => Operator::LogicalAnd,
-                    TokenType::LogicalOr => Operator::LogicalOr,
-                    _ => unreachable!(),
-                };
-                tokens.next();
-
-                let right = parse_relational_expression(tokens)?;
-                left = Expression::BinaryExpression {
-                    left: Box::new(left),
-                    operator: op,
-                    right: Box::new(right),
-                };
-            }
-            _ => break,
+    let mut left = parse_unary_expression(tokens)?;
+
+    while let Some((operator, precedence)) = tokens.peek().and_then(|token| binary_operator(&token.token_type)) {
+        if precedence < min_precedence {
+            break;
         }
+        let Some(&Token { line, column, .. }) = tokens.next() else { break }; // consume the operator
+
+        // Right-associative `**` lets its right side hold another `**`
+        let next_min = match operator {
+            Operator::Power => precedence,
+            _ => precedence + 1,
+        };
+        let right = parse_binary_expression(tokens, next_min)?;
+        left = Expression::BinaryExpression {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            line,
+            column,
+        };
     }
     Some(left)
 }
 
-pub fn parse_relational_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
+fn parse_unary_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
 where
     T: Iterator<Item = &'a Token>,
 {
-    let mut left = parse_additive_expression(tokens)?;
-
-    while let Some(token) = tokens.peek() {
-        match token.token_type {
-            TokenType::EqualTwo |
-            TokenType::NotEqual |
-            TokenType::Rchevr |
-            TokenType::RchevrEq |
-            TokenType::Lchevr |
-            TokenType::LchevrEq => {
-                let op = match token.token_type {
-                    TokenType::EqualTwo => Operator::Equal,
-                    TokenType::NotEqual => Operator::NotEqual,
-                    TokenType::Rchevr => Operator::Greater,
-                    TokenType::RchevrEq => Operator::GreaterEqual,
-                    TokenType::Lchevr => Operator::Less,
-                    TokenType::LchevrEq => Operator::LessEqual,
-                    _ => unreachable!(),
-                };
-                tokens.next();
-
-                let right = parse_additive_expression(tokens)?;
-                left = Expression::BinaryExpression {
-                    left: Box::new(left),
-                    operator: op,
-                    right: Box::new(right),
-                };
-            }
-            _ => break,
-        }
+    if let Some(&&Token { token_type: TokenType::Minus, line, column, .. }) = tokens.peek() {
+        tokens.next(); // consume '-'
+
+        let operand = parse_binary_expression(tokens, UNARY_OPERAND)?;
+        return Some(Expression::UnaryExpression {
+            operator: Operator::Subtract,
+            operand: Box::new(operand),
+            line,
+            column,
+        });
     }
-    Some(left)
-}
 
-pub fn parse_additive_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
-where
-    T: Iterator<Item = &'a Token>,
-{
-    let mut left = parse_multiplicative_expression(tokens)?;
-
-    while let Some(token) = tokens.peek() {
-        match token.token_type {
-            TokenType::Plus | TokenType::Minus => {
-                let op = match token.token_type {
-                    TokenType::Plus => Operator::Add,
-                    TokenType::Minus => Operator::Subtract,
-                    _ => unreachable!(),
-                };
-                tokens.next();
-
-                let right = parse_multiplicative_expression(tokens)?;
-                left = Expression::BinaryExpression {
-                    left: Box::new(left),
-                    operator: op,
-                    right: Box::new(right),
-                };
-            }
-            _ => break,
-        }
+    if let Some(&&Token { token_type: TokenType::Not, line, column, .. }) = tokens.peek() {
+        let keyword = tokens.next()?.lexeme == "not"; // consume '!' or 'not'
+
+        let operand = parse_binary_expression(tokens, if keyword { NOT_OPERAND } else { UNARY_OPERAND })?;
+        return Some(Expression::UnaryExpression {
+            operator: Operator::Not,
+            operand: Box::new(operand),
+            line,
+            column,
+        });
     }
-    Some(left)
+
+    parse_postfix_expression(tokens)
 }
 
-pub fn parse_multiplicative_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
+// `value.method(args)` calls `method(value, args)` and `value[index]` reads
+// an element or a map key; both chain left to right
+pub fn parse_postfix_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
 where
     T: Iterator<Item = &'a Token>,
 {
-    let mut left = parse_primary_expression(tokens)?;
-
-    while let Some(token) = tokens.peek() {
-        match token.token_type {
-            TokenType::Star | TokenType::Div => {
-                let op = match token.token_type {
-                    TokenType::Star => Operator::Multiply,
-                    TokenType::Div => Operator::Divide,
-                    _ => unreachable!(),
-                };
-                tokens.next();
-
-                let right = parse_primary_expression(tokens)?;
-                left = Expression::BinaryExpression {
-                    left: Box::new(left),
-                    operator: op,
-                    right: Box::new(right),
-                };
+    let mut expr = parse_primary_expression(tokens)?;
+
+    loop {
+        if let Some(&&Token { token_type: TokenType::Lbrack, line, column, .. }) = tokens.peek() {
+            tokens.next(); // consume '['
+            let index = parse_expression(tokens)?;
+            let token = tokens.next();
+            if !matches!(token, Some(Token { token_type: TokenType::Rbrack, .. })) {
+                report_at(token, LoaErrorKind::ExpectedToken("]".to_string()),
+                          format!("Expected ']' after the index, found {}", describe(token)));
+                return None;
             }
-            _ => break,
+            expr = Expression::Index { target: Box::new(expr), index: Box::new(index), line, column };
+            continue;
         }
+
+        if tokens.next_if(|token| token.token_type == TokenType::Dot).is_none() {
+            break;
+        }
+
+        let (method, line, column) = match tokens.next() {
+            Some(Token { token_type: TokenType::Identifier(name), line, column, .. }) => (name.clone(), *line, *column),
+            token => {
+                report_at(token, LoaErrorKind::ExpectedToken("method name".to_string()),
+                          format!("Expected a method name after '.', found {}", describe(token)));
+                return None;
+            }
+        };
+
+        let Expression::FunctionCall { name, args, .. } = parse_function_call(Some(method), line, column, tokens)? else {
+            return None;
+        };
+        expr = Expression::MethodCall {
+            receiver: Box::new(expr),
+            method: name,
+            args,
+            line,
+            column,
+        };
     }
-    Some(left)
+
+    Some(expr)
 }
 
 pub fn parse_primary_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
 where
     T: Iterator<Item = &'a Token>,
 {
-    let token = tokens.peek()?.clone();
+    let token = (*tokens.peek()?).clone();
 
     match &token.token_type {
         TokenType::Number(value) => {
@@ -180,33 +211,29 @@ where
             tokens.next();
             Some(Expression::Literal(Literal::Float(*value)))
         }
+        TokenType::Bool(value) => {
+            tokens.next();
+            Some(Expression::Literal(Literal::Bool(*value)))
+        }
+        TokenType::None => {
+            tokens.next();
+            Some(Expression::Literal(Literal::None))
+        }
         TokenType::Identifier(name) => {
             let name = name.clone();
             tokens.next(); // consume identifier
 
             if let Some(Token { token_type: TokenType::Lparen, .. }) = tokens.peek() {
-                tokens.next(); // consume '('
-
-                let mut args = vec![];
-                while let Some(token) = tokens.peek() {
-                    if token.token_type == TokenType::Rparen {
-                        tokens.next(); // consume ')'
-                        break;
-                    }
-
-                    let arg = parse_expression(tokens)?;
-                    args.push(arg);
-
-                    if let Some(Token { token_type: TokenType::Comma, .. }) = tokens.peek() {
-                        tokens.next(); // consume ','
-                    }
-                }
-
-                Some(Expression::FunctionCall { name, args })
+                parse_function_call(Some(name), token.line, token.column, tokens)
             } else {
                 Some(Expression::Variable(name))
             }
         }
+        // `input` lexes as a keyword but is called like a built-in
+        TokenType::Input => {
+            tokens.next(); // consume 'input'
+            parse_function_call(Some("input".to_string()), token.line, token.column, tokens)
+        }
         TokenType::Lparen => {
             parse_parenthesized_expression(tokens).map(|expr| Expression::Grouped(Box::new(expr)))
         }
@@ -214,36 +241,126 @@ where
             tokens.next(); // consume the string token
             Some(Expression::Literal(Literal::String(value.clone())))
         }
+        TokenType::InterpolatedString(parts) => {
+            tokens.next(); // consume the string token
+            let mut pieces = Vec::with_capacity(parts.len());
+            for part in parts {
+                match part {
+                    StringPart::Text(text) => pieces.push(InterpolationPart::Text(text.clone())),
+                    StringPart::Code(code) => pieces.push(InterpolationPart::Expression(parse_interpolation(code)?)),
+                }
+            }
+            Some(Expression::Interpolation(pieces))
+        }
+        TokenType::Lbrack => {
+            tokens.next(); // consume '['
+
+            let mut elements = vec![];
+            loop {
+                if let Some(Token { token_type: TokenType::Rbrack, .. }) = tokens.peek() {
+                    tokens.next(); // consume ']'
+                    break;
+                }
+
+                elements.push(parse_expression(tokens)?);
+
+                match tokens.next() {
+                    Some(Token { token_type: TokenType::Comma, .. }) => continue,
+                    Some(Token { token_type: TokenType::Rbrack, .. }) => break,
+                    token => {
+                        report_at(token, LoaErrorKind::ExpectedToken("',' or ']'".to_string()),
+                                  "Expected ',' or ']' in array literal");
+                        return None;
+                    }
+                }
+            }
+
+            Some(Expression::ArrayLiteral(elements))
+        }
+        TokenType::Lbrace => {
+            tokens.next(); // consume '{'
+
+            let mut entries = vec![];
+            loop {
+                if let Some(Token { token_type: TokenType::Rbrace, .. }) = tokens.peek() {
+                    tokens.next(); // consume '}'
+                    break;
+                }
+
+                let key = parse_expression(tokens)?;
+                let token = tokens.next();
+                if !matches!(token, Some(Token { token_type: TokenType::Colon, .. })) {
+                    report_at(token, LoaErrorKind::ExpectedToken(":".to_string()),
+                              format!("Expected ':' after a map key, found {}", describe(token)));
+                    return None;
+                }
+                entries.push((key, parse_expression(tokens)?));
+
+                match tokens.next() {
+                    Some(Token { token_type: TokenType::Comma, .. }) => continue,
+                    Some(Token { token_type: TokenType::Rbrace, .. }) => break,
+                    token => {
+                        report_at(token, LoaErrorKind::ExpectedToken("',' or '}'".to_string()),
+                                  format!("Expected ',' or '}}' in map literal, found {}", describe(token)));
+                        return None;
+                    }
+                }
+            }
+
+            Some(Expression::MapLiteral(entries))
+        }
         _ => {
-            println!("Error: Expected primary expression, found {:?}", token.token_type);
+            report_at(Some(&token), LoaErrorKind::UnexpectedToken(token.lexeme.clone()),
+                      format!("Expected an expression, found {}", describe(Some(&token))));
             None
         }
     }
 }
 
+// The expression inside one `${...}`, which must run up to its `}`
+fn parse_interpolation(code: &[Token]) -> Option<Expression> {
+    let mut tokens = code.iter().peekable();
+    let expr = parse_expression(&mut tokens)?;
+    match tokens.next() {
+        Some(Token { token_type: TokenType::Rbrace, .. }) => Some(expr),
+        token => {
+            report_at(token, LoaErrorKind::ExpectedToken("}".to_string()),
+                      format!("Expected '}}' to end the interpolation, found {}", describe(token)));
+            None
+        }
+    }
+}
+
+// A grouping `( expr )`, reached only when no name precedes the `(`: a name
+// followed by `(` is a call and is handled by `parse_function_call`
 pub fn parse_parenthesized_expression<'a, T>(tokens: &mut Peekable<T>) -> Option<Expression>
 where
     T: Iterator<Item = &'a Token>,
 {
     // Ensure the next token is '('
-    if tokens.next()?.token_type != TokenType::Lparen {
-        println!("Error: Expected '('");
-        return None;
-    }
+    let open = match tokens.next() {
+        Some(token @ Token { token_type: TokenType::Lparen, .. }) => token,
+        token => {
+            report_at(token, LoaErrorKind::ExpectedToken("(".to_string()), "Expected '('");
+            return None;
+        }
+    };
 
     // Parse the inner expression
     let expr = parse_expression(tokens)?;
 
     // Ensure the next token is ')'
-    if tokens.next()?.token_type != TokenType::Rparen {
-        println!("Error: Expected ')'");
+    let token = tokens.next();
+    if !matches!(token, Some(Token { token_type: TokenType::Rparen, .. })) {
+        report_at(token, LoaErrorKind::ExpectedToken(")".to_string()),
+                  format!("Expected ')' to close the '(' at {}:{}, found {}", open.line, open.column, describe(token)));
         return None;
     }
 
     Some(expr)
 }
 
-pub fn parse_expression_from_token(first_token: &Token, tokens: &mut Peekable<Iter<Token>>) -> Option<Expression> {
+pub fn parse_expression_from_token(first_token: &Token, _tokens: &mut Peekable<Iter<Token>>) -> Option<Expression> {
     match &first_token.token_type {
         TokenType::Identifier(name) => Some(Expression::Variable(name.clone())),
 
@@ -1,5 +1,7 @@
+#[allow(clippy::module_inception)]
 mod parser;
 pub mod ast;
 mod format;
 
-pub use parser::*;
\ No newline at end of file
+pub use parser::*;
+pub use format::parse_format_string;
\ No newline at end of file
@@ -1,30 +1,125 @@
+use std::cell::{Cell, RefCell};
 use std::collections::HashSet;
 use std::iter::Peekable;
 use std::slice::Iter;
 use ::lexer::*;
-use error::{LoaError, LoaErrorKind};
-use parser::ast::*;
-use crate::*;
+use error::{current_file, LoaError, LoaErrorKind};
+use crate::ast::*;
 use crate::parser::format::*;
 
-pub fn parse(tokens: &Vec<Token>) -> Option<Vec<ASTNode>> {
+thread_local! {
+    // Whether simple statements must end with `;` (`loa run --require-semicolons`)
+    static REQUIRE_SEMICOLONS: Cell<bool> = const { Cell::new(false) };
+    // Errors found by the current `parse` call, returned together at the end
+    static ERRORS: RefCell<Vec<LoaError>> = const { RefCell::new(Vec::new()) };
+}
+
+pub fn set_require_semicolons(required: bool) {
+    REQUIRE_SEMICOLONS.with(|flag| flag.set(required));
+}
+
+pub fn parse(tokens: &[Token]) -> Result<Vec<ASTNode>, Vec<LoaError>> {
+    ERRORS.with(|errors| errors.borrow_mut().clear());
+
+    // Report every lexing error up front rather than stopping at the first
+    let mut has_lex_error = false;
+    for token in tokens {
+        let (kind, message) = match &token.token_type {
+            TokenType::Error(message) => (LoaErrorKind::SyntaxError("Invalid token".to_string()), message),
+            TokenType::IndentationError(message) => (LoaErrorKind::IndentationError, message),
+            _ => continue,
+        };
+        report(LoaError::new(kind, message.clone(), current_file(), token.line, token.column));
+        has_lex_error = true;
+    }
+    if has_lex_error {
+        return Err(take_errors());
+    }
+
     let mut iter = tokens.iter().peekable();
     let mut nodes = vec![];
 
     while let Some(token) = iter.peek() {
-        if token.token_type == TokenType::Eof {
-            break;
+        match token.token_type {
+            TokenType::Eof => break,
+            // A line break left over after a `;`-terminated statement
+            TokenType::Newline => {
+                iter.next();
+                continue;
+            }
+            _ => {}
         }
 
+        let start = tokens.len() - iter.len();
+        let reported = ERRORS.with(|errors| errors.borrow().len());
+
         if let Some(node) = parse_statement(&mut iter) {
             nodes.push(node);
-        } else {
-            println!("❌ Failed to parse statement");
-            return None;
+            continue;
         }
+
+        if ERRORS.with(|errors| errors.borrow().len()) == reported {
+            report(LoaError::new(
+                LoaErrorKind::SyntaxError("Invalid statement".to_string()),
+                "Failed to parse statement",
+                current_file(),
+                tokens[start].line,
+                tokens[start].column,
+            ));
+        }
+
+        // Resume at the next statement so later errors are reported too
+        iter = tokens[next_statement(tokens, start)..].iter().peekable();
     }
 
-    Some(nodes)
+    let errors = take_errors();
+    if errors.is_empty() {
+        Ok(nodes)
+    } else {
+        Err(errors)
+    }
+}
+
+pub(crate) fn report(error: LoaError) {
+    ERRORS.with(|errors| errors.borrow_mut().push(error));
+}
+
+// Reports a parse error at `token`; without one (end of input) it has no position
+pub(crate) fn report_at(token: Option<&Token>, kind: LoaErrorKind, message: impl Into<String>) {
+    let (line, column) = token.map_or((0, 0), |token| (token.line, token.column));
+    report(LoaError::new(kind, message, current_file(), line, column));
+}
+
+fn take_errors() -> Vec<LoaError> {
+    ERRORS.with(|errors| std::mem::take(&mut *errors.borrow_mut()))
+}
+
+// How a token is named in "found ..." messages
+pub(crate) fn describe(token: Option<&Token>) -> String {
+    match token {
+        None | Some(Token { token_type: TokenType::Eof, .. }) => "end of input".to_string(),
+        Some(Token { token_type: TokenType::Newline | TokenType::Indent | TokenType::Dedent, .. }) => "end of line".to_string(),
+        Some(token) => token.token_type.to_string(),
+    }
+}
+
+// Index of the first token after the statement starting at `start`: the
+// next line that is back at the statement's own indentation
+fn next_statement(tokens: &[Token], start: usize) -> usize {
+    let line = tokens[start].line;
+    let mut depth = 0usize;
+
+    for (index, token) in tokens.iter().enumerate().skip(start) {
+        match token.token_type {
+            TokenType::Indent => depth += 1,
+            TokenType::Dedent => depth = depth.saturating_sub(1),
+            TokenType::Eof => return index,
+            _ if depth == 0 && token.line > line => return index,
+            _ => {}
+        }
+    }
+
+    tokens.len()
 }
 
 pub fn param(parameter: String, initial_value: Option<Value>) -> ParameterNode {
@@ -34,84 +129,87 @@ pub fn param(parameter: String, initial_value: Option<Value>) -> ParameterNode {
     }
 }
 
-pub fn parse_parameters(tokens: &mut Peekable<Iter<Token>>) -> Vec<ParameterNode> {
+// `(a, b: = 1; c,)`: parameters are separated by ',' or ';', a trailing
+// separator is allowed and the ':' after a name is optional
+pub fn parse_parameters(tokens: &mut Peekable<Iter<Token>>) -> Option<Vec<ParameterNode>> {
     let mut params = vec![];
 
     loop {
-        let Some(token) = tokens.peek() else {
-            break;
-        };
-
-        match &token.token_type {
-            TokenType::Identifier(name) => {
-                let name = name.clone();
-                tokens.next(); // consume identifier
+        if tokens.next_if(|token| token.token_type == TokenType::Rparen).is_some() {
+            return Some(params);
+        }
 
-                if !matches!(tokens.peek().map(|t| &t.token_type), Some(TokenType::Colon)) {
-                    println!("Error: Expected ':' after parameter name '{}'", name);
-                    break;
-                }
-                tokens.next(); // consume ':'
-
-                let initial_value = if matches!(tokens.peek().map(|t| &t.token_type), Some(TokenType::Equal)) {
-                    tokens.next(); // consume '='
-                    match tokens.next() {
-                        Some(Token { token_type: TokenType::Number(n), .. }) => Some(Value::Int(*n)),
-                        Some(Token { token_type: TokenType::Float(f), .. }) => Some(Value::Float(*f)),
-                        Some(Token { token_type: TokenType::String(s), .. }) => Some(Value::Text(s.clone())),
-                        _ => None,
-                    }
-                } else {
-                    None
-                };
+        let name = match tokens.next() {
+            Some(Token { token_type: TokenType::Identifier(name), .. }) => name.clone(),
+            token => {
+                report_at(token, LoaErrorKind::ExpectedToken("parameter name".to_string()),
+                          format!("Expected a parameter name or ')', found {}", describe(token)));
+                return None;
+            }
+        };
 
-                params.push(ParameterNode {
-                    name,
-                    initial_value,
-                });
+        tokens.next_if(|token| token.token_type == TokenType::Colon);
 
-                match tokens.peek().map(|t| &t.token_type) {
-                    Some(TokenType::SemiColon) => {
-                        tokens.next(); // consume ';'
-                        continue;
-                    }
-                    Some(TokenType::Rparen) => {
-                        tokens.next();
-                        break;
-                    }
-                    Some(TokenType::Comma) => {
-                        println!("Error: use `;` instead of `,` to separate parameters");
-                        break;
-                    }
-                    _ => break,
-                }
-            }
+        let initial_value = if tokens.next_if(|token| token.token_type == TokenType::Equal).is_some() {
+            Some(parse_default_value(tokens, &name)?)
+        } else {
+            None
+        };
 
-            TokenType::Rparen => {
-                tokens.next();
-                break;
+        params.push(ParameterNode {
+            name,
+            initial_value,
+        });
+
+        match tokens.next() {
+            Some(Token { token_type: TokenType::Comma | TokenType::SemiColon, .. }) => continue,
+            Some(Token { token_type: TokenType::Rparen, .. }) => return Some(params),
+            token => {
+                report_at(token, LoaErrorKind::ExpectedToken("',' or ')'".to_string()),
+                          format!("Expected ',' or ')' after a parameter, found {}", describe(token)));
+                return None;
             }
-
-            _ => break,
         }
     }
+}
 
-    params
+// A parameter default is a single literal, optionally a negative number
+fn parse_default_value(tokens: &mut Peekable<Iter<Token>>, param: &str) -> Option<Value> {
+    let negative = tokens.next_if(|token| token.token_type == TokenType::Minus).is_some();
+
+    match tokens.next() {
+        Some(Token { token_type: TokenType::Number(n), .. }) => Some(Value::Int(if negative { -n } else { *n })),
+        Some(Token { token_type: TokenType::Float(f), .. }) => Some(Value::Float(if negative { -f } else { *f })),
+        Some(Token { token_type: TokenType::String(s), .. }) if !negative => Some(Value::Text(s.clone())),
+        Some(Token { token_type: TokenType::Bool(b), .. }) if !negative => Some(Value::Bool(*b)),
+        token => {
+            report_at(token, LoaErrorKind::ExpectedToken("literal".to_string()),
+                      format!("Expected a number, string or boolean as the default of '{}', found {}", param, describe(token)));
+            None
+        }
+    }
 }
 
 pub fn extract_body(tokens: &mut Peekable<Iter<Token>>) -> Option<Vec<ASTNode>> {
     let mut body = vec![];
 
     if tokens.peek()?.token_type != TokenType::Colon {
-        println!("Error: Expected ':' before function body");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                  "Expected ':' before function body");
         return None;
     }
-    tokens.next(); // consume '{'
+    tokens.next(); // consume ':'
+    if tokens.peek()?.token_type == TokenType::Lbrace {
+        return parse_brace_block(tokens);
+    }
+    tokens.next_if(|token| token.token_type == TokenType::Newline);
 
     if tokens.peek()?.token_type != TokenType::Indent {
-        println!("Error: Expected Indent after ':' for function body");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("indented block".to_string()),
+                  "Expected an indented function body after ':'");
         return None;
     }
+    tokens.next(); // consume Indent
 
     while let Some(token) = tokens.peek() {
         match &token.token_type {
@@ -119,51 +217,52 @@ pub fn extract_body(tokens: &mut Peekable<Iter<Token>>) -> Option<Vec<ASTNode>>
                 tokens.next(); // consume Dedent
                 break;
             }
+            TokenType::Newline => {
+                tokens.next();
+            }
             TokenType::Eof => {
-                println!("Error: Unexpected EOF inside function body");
+                report_at(Some(token), LoaErrorKind::SyntaxError("Unexpected end of input".to_string()),
+                          "Unexpected end of input inside function body");
                 return None;
             }
-            _ => {
-                if let Some(node) = parse_statement(tokens) {
-                    body.push(node);
-                } else {
-                    println!("Error: Failed to parse statement inside function body");
-                    return None;
-                }
-            }
+            _ => body.push(parse_statement(tokens)?),
         }
     }
 
     Some(body)
 }
 
-pub fn parse_function_call(name: Option<String>, tokens: &mut Peekable<Iter<Token>>) -> Option<Expression> {
+// `line`/`column` locate the already consumed name, for runtime errors
+pub fn parse_function_call<'a, T>(name: Option<String>, line: usize, column: usize, tokens: &mut Peekable<T>) -> Option<Expression>
+where
+    T: Iterator<Item = &'a Token>,
+{
     let name = name?;
 
     if tokens.peek()?.token_type != TokenType::Lparen {
-        println!("❌ Expected '(' after function name '{}'", name);
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("(".to_string()),
+                  format!("Expected '(' after function name '{}'", name));
         return None;
     }
     tokens.next(); // consume '('
 
     let mut args = vec![];
 
-    while let Some(token) = tokens.peek() {
-        if token.token_type == TokenType::Rparen {
-            tokens.next(); // consume ')'
-            break;
-        }
-
-        let arg = parse_expression(tokens)?;
-        args.push(arg);
+    if let Some(Token { token_type: TokenType::Rparen, .. }) = tokens.peek() {
+        tokens.next(); // consume ')'
+        return Some(Expression::FunctionCall { name, args, line, column });
+    }
 
-        match tokens.peek().map(|t| &t.token_type) {
-            Some(TokenType::Comma) => {
-                tokens.next(); // consume ','
-            }
-            Some(TokenType::Rparen) => continue,
-            _ => {
-                println!("❌ Unexpected token in function arguments: {:?}", tokens.peek());
+    // arg (',' arg)* ')'
+    loop {
+        args.push(parse_expression(tokens)?);
+
+        match tokens.next() {
+            Some(Token { token_type: TokenType::Comma, .. }) => continue,
+            Some(Token { token_type: TokenType::Rparen, .. }) => break,
+            token => {
+                report_at(token, LoaErrorKind::ExpectedToken("',' or ')'".to_string()),
+                          format!("Expected ',' or ')' in call to '{}', found {}", name, describe(token)));
                 return None;
             }
         }
@@ -172,60 +271,42 @@ pub fn parse_function_call(name: Option<String>, tokens: &mut Peekable<Iter<Toke
     Some(Expression::FunctionCall {
         name,
         args,
+        line,
+        column,
     })
 }
 
-fn parse_parentheses(tokens: &mut Peekable<Iter<Token>>) -> Vec<Token> {
-    let mut param_tokens = vec![];
-    let mut paren_depth = 1;
-
-    while let Some(token) = tokens.next() {
-        match token.token_type {
-            TokenType::Lparen => paren_depth += 1,
-            TokenType::Rparen => {
-                paren_depth -= 1;
-                if paren_depth == 0 {
-                    break;
-                }
-            }
-            _ => {}
-        }
-        param_tokens.push(token.clone());
-    }
-    param_tokens
-}
-
 // FUN parsing
 fn parse_function(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
     tokens.next(); // consume 'fun'
 
     let name = match tokens.next() {
         Some(Token { token_type: TokenType::Identifier(name), .. }) => name.clone(),
-        _ => return None,
+        token => {
+            report_at(token, LoaErrorKind::ExpectedToken("function name".to_string()),
+                      "Expected a function name after 'fun'");
+            return None;
+        }
     };
 
     if tokens.peek()?.token_type != TokenType::Lparen {
-        println!("Error: Expected '(' after function name '{}'", name);
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("(".to_string()),
+                  format!("Expected '(' after function name '{}'", name));
         return None;
     }
     tokens.next(); // consume '('
 
-    let parameters = parse_parameters(tokens);
+    let parameters = parse_parameters(tokens)?;
 
     let mut param_names = HashSet::new();
     for param in &parameters {
         if !param_names.insert(param.name.clone()) {
-            println!("Error: Parameter '{}' is declared multiple times", param.name);
+            report_at(tokens.peek().copied(), LoaErrorKind::SyntaxError("Duplicate parameter".to_string()),
+                      format!("Parameter '{}' is declared multiple times in '{}'", param.name, name));
             return None;
         }
     }
 
-    if tokens.peek()?.token_type != TokenType::Colon {
-        println!("Error: Expected ':' after function parameters");
-        return None;
-    }
-    tokens.next(); // consume ':'
-
     let body = extract_body(tokens)?;
 
     Some(ASTNode::Function(FunctionNode {
@@ -236,185 +317,279 @@ fn parse_function(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
 }
 
 // VAR parsing
-fn parse_var(tokens: &mut Peekable<Iter<'_, Token>>) -> Option<ASTNode> {
+// `var x = value` (or `let`) always binds in the current scope, where a plain
+// assignment updates whichever binding already exists
+fn parse_var(tokens: &mut Peekable<Iter<'_, Token>>, mutability: Mutability) -> Option<ASTNode> {
     let name = match tokens.next() {
         Some(Token { token_type: TokenType::Identifier(name), .. }) => name.clone(),
-        _ => {
-            println!("Expected identifier after 'var'");
+        token => {
+            report_at(token, LoaErrorKind::ExpectedToken("variable name".to_string()),
+                      "Expected a variable name after 'var', 'let' or 'const'");
             return None;
         }
     };
 
     if tokens.peek()?.token_type != TokenType::Equal {
-        println!("Expected '=' after variable name '{}'", name);
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("=".to_string()),
+                  format!("Expected '=' after variable name '{}'", name));
         return None;
     }
     tokens.next(); // consume '='
 
     let initial_value = parse_expression(tokens)?;
 
-    if let Some(Token { token_type: TokenType::SemiColon, .. }) = tokens.peek() {
-        tokens.next(); // consume ';'
-    }
-
-    Some(ASTNode::Statement(StatementNode::Assign {
-        variable: name,
-        value: initial_value,
+    Some(ASTNode::Variable(VariableNode {
+        name,
+        initial_value: Some(initial_value),
+        mutability,
     }))
 }
 
 // PRINT parsing
-fn parse_print(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+// `print(a, b, sep=", ", end="")`; `sep` and `end` may come in any order,
+// but after the values. `name` is `print` or `println`
+fn parse_print(tokens: &mut Peekable<Iter<Token>>, name: &str) -> Option<ASTNode> {
     if tokens.peek()?.token_type != TokenType::Lparen {
-        println!("Error: Expected '(' after 'print'");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("(".to_string()),
+                  format!("Expected '(' after '{}'", name));
         return None;
     }
     tokens.next(); // consume '('
 
     let mut args = Vec::new();
+    let mut sep = None;
+    let mut end = None;
 
-    while let Some(token) = tokens.peek() {
+    while let Some(&token) = tokens.peek() {
         if token.token_type == TokenType::Rparen {
             tokens.next(); // consume ')'
             break;
         }
 
-        if let Some(expr) = parse_expression(tokens) {
-            args.push(expr);
-        } else {
-            println!("Error: Failed to parse expression in 'print'");
-            return None;
+        let mut lookahead = tokens.clone();
+        lookahead.next();
+        match (&token.token_type, lookahead.peek().map(|t| &t.token_type)) {
+            (TokenType::Identifier(option), Some(TokenType::Equal)) => {
+                let option_token = tokens.next()?;
+                tokens.next(); // consume '='
+                let slot = match option.as_str() {
+                    "sep" => &mut sep,
+                    "end" => &mut end,
+                    _ => {
+                        report_at(Some(option_token), LoaErrorKind::SyntaxError("Unknown print option".to_string()),
+                                  format!("'{}' accepts only 'sep=' and 'end=', found '{}='", name, option));
+                        return None;
+                    }
+                };
+                if slot.is_some() {
+                    report_at(Some(option_token), LoaErrorKind::SyntaxError("Repeated print option".to_string()),
+                              format!("'{}=' is given more than once", option));
+                    return None;
+                }
+                *slot = Some(parse_expression(tokens)?);
+            }
+            _ if sep.is_some() || end.is_some() => {
+                report_at(Some(token), LoaErrorKind::SyntaxError("Value after print option".to_string()),
+                          format!("Values to '{}' must come before 'sep=' and 'end='", name));
+                return None;
+            }
+            _ => args.push(parse_expression(tokens)?),
         }
 
-        if let Some(Token { token_type: TokenType::Comma, .. }) = tokens.peek() {
-            tokens.next(); // consume ','
+        match tokens.peek() {
+            Some(Token { token_type: TokenType::Comma, .. }) => {
+                tokens.next(); // consume ','
+            }
+            Some(Token { token_type: TokenType::Rparen, .. }) => {}
+            token => {
+                report_at(token.copied(), LoaErrorKind::ExpectedToken("',' or ')'".to_string()),
+                          format!("Expected ',' or ')' in '{}', found {}", name, describe(token.copied())));
+                return None;
+            }
         }
     }
 
-    Some(ASTNode::Statement(StatementNode::PrintArgs(args)))
-}
-
-fn skip_whitespace(tokens: &mut Peekable<Iter<Token>>) {
-    while let Some(token) = tokens.peek() {
-        if token.token_type == TokenType::Whitespace {
-            tokens.next();
-        } else {
-            break;
-        }
-    }
+    Some(ASTNode::Statement(StatementNode::PrintArgs { args, sep, end }))
 }
 
 // IF parsing
-fn parse_if(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+// `(condition): block`, shared by `if`, `else if` and `elif`; `keyword` is
+// the one that introduced it, for error messages
+fn parse_if_branch(tokens: &mut Peekable<Iter<Token>>, keyword: &str) -> Option<(Expression, Vec<ASTNode>)> {
     if tokens.peek()?.token_type != TokenType::Lparen {
-        let token = tokens.peek().unwrap();
-        LoaError::new(
-            LoaErrorKind::ExpectedToken("(".to_string()),
-            "Expected '(' after 'if'".to_string(),
-            "unknown",
-            token.line,
-            0,
-        ).display();
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("(".to_string()),
+                  format!("Expected '(' after '{}'", keyword));
         return None;
     }
-    tokens.next(); // Consume '('
+    tokens.next(); // consume '('
 
     let condition = parse_expression(tokens)?;
 
     if tokens.peek()?.token_type != TokenType::Rparen {
-        println!("Error: Expected ')' after 'if' condition");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(")".to_string()),
+                  format!("Expected ')' after '{}' condition", keyword));
         return None;
     }
-    tokens.next(); // Consume ')'
+    tokens.next(); // consume ')'
 
     if tokens.peek()?.token_type != TokenType::Colon {
-        println!("Error: Expected ':' after 'if' condition");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                  format!("Expected ':' after '{}' condition", keyword));
         return None;
     }
-    tokens.next(); // Consume ':'
+    tokens.next(); // consume ':'
 
     let body = parse_block(tokens)?;
 
-    let mut else_if_blocks: Vec<ASTNode> = Vec::new();
+    Some((condition, body))
+}
+
+fn parse_if(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    let mut branches = vec![parse_if_branch(tokens, "if")?];
     let mut else_block = None;
 
     while let Some(token) = tokens.peek() {
-        if token.token_type != TokenType::Else {
-            break;
-        }
-        tokens.next(); // Consume 'else'
-
-        if let Some(Token { token_type: TokenType::If, .. }) = tokens.peek() {
-            tokens.next(); // consume 'if'
-            let parsed = parse_if(tokens);
-
-            match parsed {
-                Some(ASTNode::Statement(stmt @ StatementNode::If { .. })) => {
-                    else_if_blocks.push(ASTNode::Statement(stmt));
-                }
-                Some(_) | None => {
-                    return None;
-                }
+        let keyword = match token.token_type {
+            // `elif` is shorthand for `else if`
+            TokenType::Elif => {
+                tokens.next(); // consume 'elif'
+                "elif"
             }
-        } else {
-            if tokens.peek()?.token_type != TokenType::Colon {
-                println!("Error: Expected ':' after 'else'");
-                return None;
+            TokenType::Else => {
+                tokens.next(); // consume 'else'
+                if tokens.next_if(|token| token.token_type == TokenType::If).is_none() {
+                    if tokens.peek()?.token_type != TokenType::Colon {
+                        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                                  "Expected ':' after 'else'");
+                        return None;
+                    }
+                    tokens.next(); // consume ':'
+                    else_block = Some(parse_block(tokens)?);
+                    break;
+                }
+                "else if"
             }
-            tokens.next(); // Consume ':'
-            else_block = Some(Box::new(parse_block(tokens)?));
-            break;
-        }
+            _ => break,
+        };
+        branches.push(parse_if_branch(tokens, keyword)?);
     }
 
-    Some(ASTNode::Statement(StatementNode::If {
-        condition,
-        body,
-        else_if_blocks: if else_if_blocks.is_empty() {
-            None
-        } else {
-            Some(Box::new(else_if_blocks))
-        },
-        else_block,
-    }))
+    Some(ASTNode::Statement(StatementNode::If { branches, else_block }))
 }
 
-// FOR parsing
+// `for x in expr:` binds `x` to each element of `expr` in turn
 fn parse_for(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
-    /*
-    // Check 'for' keyword and see if there is '()
-    if tokens.peek()?.token_type != TokenType::Lparen {
-        println!("Error: Expected '(' after 'if'");
+    let var = match tokens.next() {
+        Some(Token { token_type: TokenType::Identifier(name), .. }) => name.clone(),
+        token => {
+            report_at(token, LoaErrorKind::ExpectedToken("variable name".to_string()),
+                      "Expected a variable name after 'for'");
+            return None;
+        }
+    };
+
+    if tokens.peek()?.token_type != TokenType::In {
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("in".to_string()),
+                  format!("Expected 'in' after 'for {}'", var));
         return None;
     }
-    tokens.next(); // '(' Consumption
+    tokens.next(); // consume 'in'
 
-    // Conditional parsing (where condition must be made ASTNode)
-    let initialization = parse_expression(tokens)?; // Parsing conditions with expressions
-    let condition = parse_expression(tokens)?;
-    let increment = parse_expression(tokens)?;
-    let body = parse_expression(tokens)?;
+    let &&Token { line, column, .. } = tokens.peek()?;
+    let iterable = parse_expression(tokens)?;
 
-    if tokens.peek()?.token_type != TokenType::Rparen {
-        println!("Error: Expected ')' after condition");
+    if tokens.peek()?.token_type != TokenType::Colon {
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                  "Expected ':' after 'for' iterable");
         return None;
     }
-    tokens.next(); // ')' Consumption
+    tokens.next(); // consume ':'
 
-    Some(ASTNode::Statement(StatementNode::For {
-        initialization,
-        condition,
-        increment,
-        body,
-    }))
-     */
-    None
+    let body = parse_block(tokens)?;
+
+    Some(ASTNode::Statement(StatementNode::ForIn { var, iterable, body, line, column }))
+}
+
+// `match x:` followed by an indented list of `case value:` arms and at
+// most one `default:` arm, each with its own block
+fn parse_match(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    let scrutinee = parse_expression(tokens)?;
+
+    if tokens.peek()?.token_type != TokenType::Colon {
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                  "Expected ':' after 'match' value");
+        return None;
+    }
+    tokens.next(); // consume ':'
+
+    // The arms are indented, or enclosed in braces
+    let braced = tokens.next_if(|token| token.token_type == TokenType::Lbrace).is_some();
+    if !braced {
+        tokens.next_if(|token| token.token_type == TokenType::Newline);
+
+        if tokens.peek()?.token_type != TokenType::Indent {
+            report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("indented block".to_string()),
+                      "Expected indented 'case' arms after 'match'");
+            return None;
+        }
+        tokens.next(); // consume Indent
+    }
+
+    let mut arms = vec![];
+    let mut default = None;
+
+    loop {
+        let token = tokens.next();
+        match token.map(|token| &token.token_type) {
+            Some(TokenType::Dedent) if !braced => break,
+            Some(TokenType::Rbrace) if braced => break,
+            Some(TokenType::Newline) => continue,
+            Some(TokenType::Indent | TokenType::Dedent) if braced => continue,
+            Some(TokenType::Case) if default.is_none() => {
+                let value = parse_expression(tokens)?;
+                expect_arm_colon(tokens, "case value")?;
+                arms.push((value, parse_block(tokens)?));
+            }
+            Some(TokenType::Default) if default.is_none() => {
+                expect_arm_colon(tokens, "'default'")?;
+                default = Some(parse_block(tokens)?);
+            }
+            Some(TokenType::Case | TokenType::Default) => {
+                report_at(token, LoaErrorKind::SyntaxError("Misplaced arm".to_string()),
+                          "The 'default' arm must be the last arm of a 'match'");
+                return None;
+            }
+            _ => {
+                report_at(token, LoaErrorKind::ExpectedToken("'case' or 'default'".to_string()),
+                          format!("Expected 'case' or 'default' in 'match', found {}", describe(token)));
+                return None;
+            }
+        }
+    }
+
+    Some(ASTNode::Statement(StatementNode::Match { scrutinee, arms, default }))
+}
+
+fn expect_arm_colon(tokens: &mut Peekable<Iter<Token>>, after: &str) -> Option<()> {
+    if tokens.peek()?.token_type != TokenType::Colon {
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                  format!("Expected ':' after {}", after));
+        return None;
+    }
+    tokens.next(); // consume ':'
+    Some(())
 }
 
 // WHILE parsing
 fn parse_while(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    if matches!(tokens.peek(), Some(Token { token_type: TokenType::Var, lexeme, .. }) if lexeme == "let") {
+        tokens.next(); // consume 'let'
+        return parse_while_let(tokens);
+    }
+
     if tokens.peek()?.token_type != TokenType::Lparen {
-        println!("Error: Expected '(' after 'while'");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("(".to_string()),
+                  "Expected '(' after 'while'");
         return None;
     }
     tokens.next(); // consume '('
@@ -422,68 +597,243 @@ fn parse_while(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
     let condition = parse_expression(tokens)?;
 
     if tokens.peek()?.token_type != TokenType::Rparen {
-        println!("Error: Expected ')' after 'while' condition");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(")".to_string()),
+                  "Expected ')' after 'while' condition");
         return None;
     }
     tokens.next(); // consume ')'
 
     if tokens.peek()?.token_type != TokenType::Colon {
-        println!("Error: Expected ':' after 'while' condition");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                  "Expected ':' after 'while' condition");
         return None;
     }
     tokens.next(); // consume ':'
 
     let body = parse_block(tokens)?;
 
-    Some(ASTNode::Statement(StatementNode::While { condition, body }))
+    let mut else_block = None;
+    if tokens.next_if(|token| token.token_type == TokenType::Else).is_some() {
+        if tokens.peek()?.token_type != TokenType::Colon {
+            report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                      "Expected ':' after 'else'");
+            return None;
+        }
+        tokens.next(); // consume ':'
+        else_block = Some(parse_block(tokens)?);
+    }
+
+    Some(ASTNode::Statement(StatementNode::While { condition, body, else_block }))
 }
 
-fn parse_return(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
-    let expr = if let Some(Token { token_type: TokenType::SemiColon, .. }) = tokens.peek() {
-        tokens.next(); // consume ';'
-        None
-    } else {
-        let value = parse_expression(tokens)?;
-        if let Some(Token { token_type: TokenType::SemiColon, .. }) = tokens.peek() {
-            tokens.next(); // consume ';'
+// `while let x = expr:` binds `x` to `expr` and loops until it evaluates to none
+fn parse_while_let(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    let variable = match tokens.next() {
+        Some(Token { token_type: TokenType::Identifier(name), .. }) => name.clone(),
+        token => {
+            report_at(token, LoaErrorKind::ExpectedToken("variable name".to_string()),
+                      "Expected a variable name after 'while let'");
+            return None;
         }
-        Some(value)
+    };
+
+    if tokens.peek()?.token_type != TokenType::Equal {
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("=".to_string()),
+                  format!("Expected '=' after 'while let {}'", variable));
+        return None;
+    }
+    tokens.next(); // consume '='
+
+    let value = parse_expression(tokens)?;
+
+    if tokens.peek()?.token_type != TokenType::Colon {
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken(":".to_string()),
+                  "Expected ':' after 'while let' value");
+        return None;
+    }
+    tokens.next(); // consume ':'
+
+    let body = parse_block(tokens)?;
+
+    Some(ASTNode::Statement(StatementNode::WhileLet { variable, value, body }))
+}
+
+fn parse_return(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    let expr = match tokens.peek().map(|t| &t.token_type) {
+        Some(TokenType::SemiColon | TokenType::Newline | TokenType::Dedent | TokenType::Eof) | None => None,
+        _ => Some(parse_expression(tokens)?),
     };
 
     Some(ASTNode::Statement(StatementNode::Return(expr)))
 }
 
+// IMPORT parsing
+fn parse_import(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    // `once` is only special right after `import`, so it stays usable as an identifier
+    let once = matches!(tokens.peek().map(|t| &t.token_type), Some(TokenType::Identifier(name)) if name == "once");
+    if once {
+        tokens.next(); // consume 'once'
+    }
+
+    let (path, line, column) = match tokens.next() {
+        Some(Token { token_type: TokenType::String(path), line, column, .. }) => (path.clone(), *line, *column),
+        token => {
+            report_at(token, LoaErrorKind::ExpectedToken("module path".to_string()),
+                      "Expected a module path string after 'import'");
+            return None;
+        }
+    };
+
+    Some(ASTNode::Statement(StatementNode::Import { path, once, line, column }))
+}
+
 fn parse_assignment(tokens: &mut Peekable<Iter<Token>>, first_token: &Token) -> Option<ASTNode> {
     let left_expr = parse_expression_from_token(first_token, tokens)?;
 
-    if tokens.peek()?.token_type != TokenType::Equal {
-        println!("Error: Expected '=' in assignment");
-        return None;
-    }
-    tokens.next(); // consume '='
+    let operator = match tokens.peek()?.token_type {
+        TokenType::Equal => Operator::Assign,
+        TokenType::LogicalOrAssign => Operator::LogicalOrAssign,
+        TokenType::NullCoalesceAssign => Operator::NullCoalesceAssign,
+        _ => {
+            report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("=".to_string()),
+                      "Expected '=' in assignment");
+            return None;
+        }
+    };
+    tokens.next(); // consume '=', '||=' or '??='
 
     let right_expr = parse_expression(tokens)?;
 
     if let Expression::Variable(name) = left_expr {
-        if let Some(Token { token_type: TokenType::SemiColon, .. }) = tokens.peek() {
-            tokens.next(); // consume ';'
+        if let Operator::Assign = operator {
+            return Some(ASTNode::Statement(StatementNode::Assign {
+                variable: name,
+                value: right_expr,
+                line: first_token.line,
+                column: first_token.column,
+            }));
         }
-        return Some(ASTNode::Statement(StatementNode::Assign {
+
+        return Some(ASTNode::Statement(StatementNode::ConditionalAssign {
             variable: name,
+            operator,
             value: right_expr,
+            line: first_token.line,
+            column: first_token.column,
         }));
     }
 
-    println!("Error: Left side of assignment must be a variable");
+    report_at(Some(first_token), LoaErrorKind::SyntaxError("Invalid assignment target".to_string()),
+              "Left side of assignment must be a variable");
     None
 }
 
+// `a, b = 1, 2`, which needs as many values as targets
+fn parse_multi_assignment(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    let first = tokens.peek().copied()?;
+    let mut targets = vec![];
+
+    loop {
+        match tokens.next() {
+            Some(Token { token_type: TokenType::Identifier(name), .. }) => targets.push(name.clone()),
+            token => {
+                report_at(token, LoaErrorKind::ExpectedToken("variable name".to_string()),
+                          format!("Expected a variable name to assign to, found {}", describe(token)));
+                return None;
+            }
+        }
+
+        match tokens.next() {
+            Some(Token { token_type: TokenType::Comma, .. }) => continue,
+            Some(Token { token_type: TokenType::Equal, .. }) => break,
+            token => {
+                report_at(token, LoaErrorKind::ExpectedToken("',' or '='".to_string()),
+                          format!("Expected ',' or '=' after an assignment target, found {}", describe(token)));
+                return None;
+            }
+        }
+    }
+
+    let mut values = vec![parse_expression(tokens)?];
+    while tokens.next_if(|token| token.token_type == TokenType::Comma).is_some() {
+        values.push(parse_expression(tokens)?);
+    }
+
+    if values.len() != targets.len() {
+        report_at(Some(first), LoaErrorKind::SyntaxError("Mismatched assignment".to_string()),
+                  format!("Cannot assign {} value(s) to {} target(s)", values.len(), targets.len()));
+        return None;
+    }
+
+    Some(ASTNode::Statement(StatementNode::MultiAssign {
+        targets,
+        values,
+        line: first.line,
+        column: first.column,
+    }))
+}
+
+// Whether `tokens`, starting at a `[`, hold `[index] =` rather than an
+// expression such as `items[0] + 1`
+fn is_index_assignment(mut tokens: Peekable<Iter<Token>>) -> bool {
+    let mut depth = 0usize;
+    for token in tokens.by_ref() {
+        match token.token_type {
+            TokenType::Lparen | TokenType::Lbrack | TokenType::Lbrace => depth += 1,
+            TokenType::Rparen | TokenType::Rbrack | TokenType::Rbrace => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    break;
+                }
+            }
+            TokenType::Newline | TokenType::Eof => return false,
+            _ => {}
+        }
+    }
+    matches!(tokens.peek(), Some(Token { token_type: TokenType::Equal, .. }))
+}
+
+// `items[index] = value`
+fn parse_index_assignment(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    let Some(Token { token_type: TokenType::Identifier(variable), .. }) = tokens.next() else {
+        return None;
+    };
+    let &Token { line, column, .. } = tokens.next()?; // consume '['
+    let index = parse_expression(tokens)?;
+
+    let token = tokens.next();
+    if !matches!(token, Some(Token { token_type: TokenType::Rbrack, .. })) {
+        report_at(token, LoaErrorKind::ExpectedToken("]".to_string()),
+                  format!("Expected ']' after the index, found {}", describe(token)));
+        return None;
+    }
+    tokens.next(); // consume '='
+
+    let value = parse_expression(tokens)?;
+    Some(ASTNode::Statement(StatementNode::IndexAssign { variable: variable.clone(), index, value, line, column }))
+}
+
+// A bare expression used as a statement, e.g. `1 + 2` in the REPL
+fn parse_expression_statement(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    let expr = parse_expression(tokens)?;
+
+    Some(ASTNode::Statement(StatementNode::Expression(expr)))
+}
+
 // block parsing
 fn parse_block(tokens: &mut Peekable<Iter<Token>>) -> Option<Vec<ASTNode>> {
     let mut body = vec![];
 
+    if tokens.peek()?.token_type == TokenType::Lbrace {
+        return parse_brace_block(tokens);
+    }
+
+    // The block starts on the line after its ':'
+    tokens.next_if(|token| token.token_type == TokenType::Newline);
+
     if tokens.peek()?.token_type != TokenType::Indent {
-        println!("Error: Expected Indent to start a block");
+        report_at(tokens.peek().copied(), LoaErrorKind::ExpectedToken("indented block".to_string()),
+                  "Expected an indented block");
         return None;
     }
     tokens.next(); // consume Indent
@@ -494,31 +844,116 @@ fn parse_block(tokens: &mut Peekable<Iter<Token>>) -> Option<Vec<ASTNode>> {
                 tokens.next(); // consume Dedent
                 break;
             }
+            TokenType::Newline => {
+                tokens.next();
+            }
             TokenType::Eof => {
-                println!("Error: Unexpected EOF inside block");
+                report_at(Some(token), LoaErrorKind::SyntaxError("Unexpected end of input".to_string()),
+                          "Unexpected end of input inside block");
                 return None;
             }
-            _ => {
-                if let Some(node) = parse_statement(tokens) {
-                    body.push(node);
-                } else {
-                    println!("Error: Failed to parse statement inside block");
-                    return None;
-                }
+            _ => body.push(parse_statement(tokens)?),
+        }
+    }
+
+    Some(body)
+}
+
+// `{ ... }` after a ':', as an alternative to an indented block. Line breaks
+// still end statements, but indentation inside the braces means nothing
+fn parse_brace_block(tokens: &mut Peekable<Iter<Token>>) -> Option<Vec<ASTNode>> {
+    let open = tokens.next()?; // consume '{'
+    let mut body = vec![];
+
+    while let Some(token) = tokens.peek() {
+        match token.token_type {
+            TokenType::Rbrace => {
+                tokens.next(); // consume '}'
+                break;
+            }
+            TokenType::Newline | TokenType::Indent | TokenType::Dedent => {
+                tokens.next();
+            }
+            TokenType::Eof => {
+                report_at(Some(token), LoaErrorKind::ExpectedToken("}".to_string()),
+                          format!("Expected '}}' to close the block opened at {}:{}", open.line, open.column));
+                return None;
             }
+            _ => body.push(parse_statement(tokens)?),
         }
     }
 
+    // `}` and a following `else` or `elif` may sit on separate lines
+    let mut lookahead = tokens.clone();
+    if lookahead.next_if(|token| token.token_type == TokenType::Newline).is_some()
+        && matches!(lookahead.peek().map(|token| &token.token_type), Some(TokenType::Else | TokenType::Elif))
+    {
+        tokens.next(); // consume the line break
+    }
+
     Some(body)
 }
 
-fn parse_statement(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
-    let token = tokens.peek()?.clone();
+// Ends a simple statement whose last token is `last`: `;` is consumed when
+// present and, with `set_require_semicolons(true)`, reported just past `last`
+// when missing. Otherwise the statement must be the last thing on its line,
+// so `x = 1 y = 2` is an error
+fn end_statement(tokens: &mut Peekable<Iter<Token>>, last: Option<&Token>) -> Option<()> {
+    if let Some(Token { token_type: TokenType::SemiColon, .. }) = tokens.peek() {
+        tokens.next(); // consume ';'
+        return Some(());
+    }
+
+    if REQUIRE_SEMICOLONS.with(Cell::get) {
+        let (line, column) = last.map_or((0, 0), |last| (last.line, last.column + last.lexeme.chars().count()));
+        report(LoaError::new(
+            LoaErrorKind::ExpectedToken(";".to_string()),
+            format!("Expected ';' after statement, found {}", describe(tokens.peek().copied())),
+            current_file(),
+            line,
+            column,
+        ));
+        return None;
+    }
 
-    match token.token_type {
-        TokenType::Print => {
-            tokens.next(); // consume 'print'
-            parse_print(tokens)
+    match tokens.peek() {
+        Some(Token { token_type: TokenType::Newline, .. }) => {
+            tokens.next(); // consume the line break
+            Some(())
+        }
+        // A `}` closing a brace block ends its last statement too
+        Some(Token { token_type: TokenType::Dedent | TokenType::Eof | TokenType::Rbrace, .. }) | None => Some(()),
+        token => {
+            let token = token.copied();
+            report_at(token, LoaErrorKind::ExpectedToken("end of line".to_string()),
+                      format!("Expected the statement to end here, found {}; put the next statement on a new line or after ';'",
+                              describe(token)));
+            None
+        }
+    }
+}
+
+fn parse_statement(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
+    let token = (*tokens.peek()?).clone();
+    let start = tokens.clone();
+
+    // Blocks end at their Dedent; everything else is a simple statement
+    let is_compound = matches!(token.token_type, TokenType::Fun | TokenType::If | TokenType::While | TokenType::For | TokenType::Match);
+
+    let node = match token.token_type {
+        TokenType::Fun => parse_function(tokens),
+        TokenType::Var | TokenType::Const => {
+            let keyword = tokens.next()?; // consume 'var', 'let' or 'const'
+            let mutability = match keyword.lexeme.as_str() {
+                "const" => Mutability::Const,
+                "let" => Mutability::Let,
+                _ => Mutability::Var,
+            };
+            parse_var(tokens, mutability)
+        }
+        TokenType::Print | TokenType::Println => {
+            let keyword = tokens.next()?; // consume 'print' or 'println'
+            parse_print(tokens, &keyword.lexeme)
         }
         TokenType::If => {
             tokens.next(); // consume 'if'
@@ -532,10 +967,18 @@ fn parse_statement(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
             tokens.next(); // consume 'for'
             parse_for(tokens)
         }
+        TokenType::Match => {
+            tokens.next(); // consume 'match'
+            parse_match(tokens)
+        }
         TokenType::Return => {
             tokens.next(); // consume 'return'
             parse_return(tokens)
         }
+        TokenType::Import => {
+            tokens.next(); // consume 'import'
+            parse_import(tokens)
+        }
         TokenType::Break => {
             tokens.next(); // consume 'break'
             Some(ASTNode::Statement(StatementNode::Break))
@@ -545,12 +988,41 @@ fn parse_statement(tokens: &mut Peekable<Iter<Token>>) -> Option<ASTNode> {
             Some(ASTNode::Statement(StatementNode::Continue))
         }
         TokenType::Identifier(_) => {
-            let first = tokens.next()?; // consume identifier
-            parse_assignment(tokens, first)
+            let mut lookahead = tokens.clone();
+            lookahead.next(); // skip identifier
+
+            match lookahead.peek().map(|t| &t.token_type) {
+                Some(TokenType::Equal | TokenType::LogicalOrAssign | TokenType::NullCoalesceAssign) => {
+                    let first = tokens.next()?; // consume identifier
+                    parse_assignment(tokens, first)
+                }
+                Some(TokenType::Comma) => parse_multi_assignment(tokens),
+                Some(TokenType::Lbrack) if is_index_assignment(lookahead) => parse_index_assignment(tokens),
+                _ => parse_expression_statement(tokens),
+            }
         }
+        TokenType::Number(_)
+        | TokenType::Float(_)
+        | TokenType::Bool(_)
+        | TokenType::None
+        | TokenType::Input
+        | TokenType::String(_)
+        | TokenType::InterpolatedString(_)
+        | TokenType::Lparen
+        | TokenType::Lbrack
+        | TokenType::Lbrace
+        | TokenType::Not => parse_expression_statement(tokens),
         _ => {
-            println!("Error: Unknown token in block: {:?}", token);
+            report_at(Some(&token), LoaErrorKind::UnexpectedToken(token.lexeme.clone()),
+                      format!("Unexpected '{}' at the start of a statement", token.lexeme));
             None
         }
+    }?;
+
+    if !is_compound {
+        let last = (start.len() - tokens.len()).checked_sub(1).and_then(|n| start.clone().nth(n));
+        end_statement(tokens, last)?;
     }
+
+    Some(node)
 }
@@ -0,0 +1,54 @@
+use lexer::Lexer;
+use parser::ast::{ASTNode, Expression, Operator, StatementNode};
+use parser::parse;
+
+fn error_messages(source: &str) -> Vec<String> {
+    match parse(&Lexer::new(source).tokenize()) {
+        Ok(_) => panic!("{:?} should not parse", source),
+        Err(errors) => errors.into_iter().map(|error| error.message).collect(),
+    }
+}
+
+#[test]
+fn parse_errors_name_the_token_found() {
+    assert_eq!(error_messages("print(1 \"two\")\n"), [
+        "Expected ',' or ')' in 'print', found string \"two\"",
+    ]);
+    assert_eq!(error_messages("total = max(3 4.5)\n"), [
+        "Expected ',' or ')' in call to 'max', found number 4.5",
+    ]);
+    assert_eq!(error_messages("value = )\n"), [
+        "Expected an expression, found ')'",
+    ]);
+    assert_eq!(error_messages("count = 1 while\n"), [
+        "Expected the statement to end here, found 'while'; put the next statement on a new line or after ';'",
+    ]);
+}
+
+#[test]
+fn if_branch_errors_name_their_keyword() {
+    assert_eq!(error_messages("if (true):\n    x = 1\nelif true:\n    x = 2\n")[0], "Expected '(' after 'elif'");
+    assert_eq!(error_messages("if (true):\n    x = 1\nelse if (false)\n    x = 2\n")[0],
+               "Expected ':' after 'else if' condition");
+    assert_eq!(error_messages("if (true:\n    x = 1\n")[0], "Expected ')' after 'if' condition");
+}
+
+fn parse_expression(source: &str) -> Expression {
+    match parse(&Lexer::new(source).tokenize()).map(|mut nodes| nodes.pop()) {
+        Ok(Some(ASTNode::Statement(StatementNode::Expression(expr)))) => expr,
+        other => panic!("not an expression: {:?}", other),
+    }
+}
+
+#[test]
+fn bang_binds_tighter_than_comparisons_and_not_looser() {
+    // `!a == b` is (!a) == b
+    let expr = parse_expression("!a == b\n");
+    assert!(matches!(&expr, Expression::BinaryExpression { operator: Operator::Equal, left, .. }
+        if matches!(**left, Expression::UnaryExpression { operator: Operator::Not, .. })), "{:?}", expr);
+
+    // `not a == b` is !(a == b)
+    let expr = parse_expression("not a == b\n");
+    assert!(matches!(&expr, Expression::UnaryExpression { operator: Operator::Not, operand, .. }
+        if matches!(**operand, Expression::BinaryExpression { operator: Operator::Equal, .. })), "{:?}", expr);
+}
@@ -1,125 +1,451 @@
 use std::{env, fs, process};
-use colorex::Colorize;
-use codegen::Interpreter;
-use lexer::Lexer;
-use parser::parse;
+use std::io::{self, Read};
+use std::sync::atomic::Ordering;
+use codegen::{parse_json, Interpreter, Json, Value, BUILTINS};
+use error::{color_enabled, explain, set_color_choice, set_current_file, set_current_source, ColorChoice, LoaError, RUNTIME_ERROR_CODES};
+use lexer::{Lexer, Token, TokenType};
+use parser::ast::{ASTNode, StatementNode};
+use parser::{parse, set_require_semicolons};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// Each Loa call nests several interpreter calls, so the main thread's
+// default stack runs out well before the interpreter's recursion limit
+const INTERPRETER_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+// Like colorex's `color`, but plain text when `--color` turns coloring off
+trait Paint {
+    fn paint(&self, rgb: &str) -> String;
+}
+
+impl Paint for str {
+    fn paint(&self, rgb: &str) -> String {
+        use colorex::Colorize;
+
+        if color_enabled() {
+            self.color(rgb).to_string()
+        } else {
+            self.to_string()
+        }
+    }
+}
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let cli = std::thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(run_cli)
+        .expect("failed to start the interpreter thread");
+    if cli.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run_cli() {
+    let args = take_color_option(env::args().collect());
 
     if args.len() < 2 {
-        eprintln!("{} {}",
-                  "Usage:".color("255,71,71"),
-                  "loa <command> [arguments]");
+        eprintln!("{} loa <command> [arguments]",
+                  "Usage:".paint("255,71,71"));
 
         eprintln!("{}",
-                  "Commands:".color("145,161,2"));
+                  "Commands:".paint("145,161,2"));
 
-        eprintln!("  {}    {}",
-                  "run <file>".color("38,139,235"),
-                  "Execute the specified Loa file");
+        eprintln!("  {}    Execute the specified Loa file",
+                  "run <file>".paint("38,139,235"));
 
-        eprintln!("  {}     {}",
-                  "--version".color("38,139,235"),
-                  "Show the CLI version");
+        eprintln!("  {}     Show the CLI version",
+                  "--version".paint("38,139,235"));
         process::exit(1);
     }
 
     match args[1].as_str() {
         "--version" | "-V" => {
             println!("{}",
-                     VERSION.color("2,161,47"));
-            return;
+                     VERSION.paint("2,161,47"));
         }
         "run" => unsafe {
+            let options = RunOptions::parse(&args[2..]);
+
+            let Some(file_path) = &options.file_path else {
+                eprintln!("{} loa run <file | -> [--ast] [--tokens] [--seed <n>] [--stdin-file <name>] [--args-json <json>] [--require-semicolons]",
+                          "Usage:".paint("255,71,71"));
+                process::exit(1);
+            };
+
+            run_loa_file(file_path, &options);
+        }
+        "-e" | "eval" => {
             if args.len() < 3 {
+                eprintln!("{} loa -e <code> [--ast] [--tokens] [--seed <n>]",
+                          "Usage:".paint("255,71,71"));
+                process::exit(1);
+            }
+
+            let options = RunOptions::parse(&args[3..]);
+            if let Some(arg) = &options.file_path {
                 eprintln!("{} {}",
-                          "Usage:".color("255,71,71"),
-                          "loa run <file>");
+                          "Unexpected argument:".paint("255,71,71"),
+                          arg);
                 process::exit(1);
             }
 
-            let file_path = &args[2];
-            run_loa_file(file_path);
+            set_current_file("<eval>");
+            run_source(&args[2], &options);
+        }
+        "check" => {
+            let options = RunOptions::parse(&args[2..]);
+
+            let Some(file_path) = &options.file_path else {
+                eprintln!("{} loa check <file> [--require-semicolons]",
+                          "Usage:".paint("255,71,71"));
+                process::exit(1);
+            };
+
+            check_loa_file(file_path, &options);
         }
         "repl" => repl_mode(),
+        "explain" => match args.get(2) {
+            Some(code) => match explain(code) {
+                Some(description) => println!("{}: {}", code.paint("38,139,235"), description),
+                None => {
+                    eprintln!("{} {}",
+                              "Unknown error code:".paint("255,71,71"),
+                              code);
+                    process::exit(1);
+                }
+            },
+            None => {
+                for (code, description) in RUNTIME_ERROR_CODES {
+                    println!("  {} {}", format!("{:<20}", code).paint("38,139,235"), description);
+                }
+            }
+        },
         "help" => {
-            println!("{}", "Options:".color("145,161,2"));
-            println!("      {}      {}",
-                     "run <file>".color("38,139,235"),
-                     "Run the Loa code.");
-            println!("      {}         {}",
-                     "repl".color("38,139,235"),
-                     "Start interactive mode.");
-
-            println!("{}", "Commands:".color("145,161,2"));
-            println!("      {}      {}\n",
-                     "-V, --version".color("38,139,235"),
-                     "Verified the version of the Loa interpreter.");
-            return;
+            println!("{}", "Options:".paint("145,161,2"));
+            println!("      {}      Run the Loa code.",
+                     "run <file>".paint("38,139,235"));
+            println!("      {}         Start interactive mode ('.help [builtin]' lists built-ins).",
+                     "repl".paint("38,139,235"));
+            println!("      {}    Check the syntax without running.",
+                     "check <file>".paint("38,139,235"));
+            println!("      {} Describe a runtime error code such as DivideByZero.",
+                     "explain [code]".paint("38,139,235"));
+            println!("      {} Run the given source code directly.",
+                     "-e, eval <code>".paint("38,139,235"));
+            println!("      {}           Print the parsed AST instead of running (with run).",
+                     "--ast".paint("38,139,235"));
+            println!("      {}        Print the lexer tokens instead of running (with run).",
+                     "--tokens".paint("38,139,235"));
+            println!("      {}      Seed random() and shuffle() for reproducible runs (with run).",
+                     "--seed <n>".paint("38,139,235"));
+            println!("      {} Name shown in errors when running stdin with 'run -'.",
+                     "--stdin-file <name>".paint("38,139,235"));
+            println!("      {} Expose a JSON value to the script through config() (with run).",
+                     "--args-json <json>".paint("38,139,235"));
+            println!("      {} Colorize output; 'auto' only on a terminal without NO_COLOR set.",
+                     "--color=<always|auto|never>".paint("38,139,235"));
+            println!("      {}                  Same as --color=never.",
+                     "--no-color".paint("38,139,235"));
+            println!("      {} Reject simple statements not ending in ';' (with run/check).",
+                     "--require-semicolons".paint("38,139,235"));
+
+            println!("{}", "Commands:".paint("145,161,2"));
+            println!("      {}      Verified the version of the Loa interpreter.\n",
+                     "-V, --version".paint("38,139,235"));
         }
         _ => {
             eprintln!("{} {}",
-                      "Unknown command:".color("255,71,71"),
+                      "Unknown command:".paint("255,71,71"),
                       args[1]);
             eprintln!("{}",
-                      "Use 'loa --version' or 'loa run <file>'".color("145,161,2"));
+                      "Use 'loa --version' or 'loa run <file>'".paint("145,161,2"));
             process::exit(1);
         }
     }
 }
 
-unsafe fn run_loa_file(file_path: &str) {
-    let code = fs::read_to_string(file_path).expect("Failed to read file");
+// `--color=always|auto|never` (or `--color <when>`, or `--no-color` for
+// `never`) may appear anywhere and applies to every command, so it is
+// removed before dispatching
+fn take_color_option(args: Vec<String>) -> Vec<String> {
+    let mut rest = Vec::with_capacity(args.len());
 
-    let mut lexer = Lexer::new(&code);
-    let tokens = lexer.tokenize();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let when = if let Some(when) = arg.strip_prefix("--color=") {
+            when.to_string()
+        } else if arg == "--color" {
+            args.next().unwrap_or_default()
+        } else if arg == "--no-color" {
+            "never".to_string()
+        } else {
+            rest.push(arg);
+            continue;
+        };
+
+        let choice = match when.as_str() {
+            "always" => ColorChoice::Always,
+            "auto" => ColorChoice::Auto,
+            "never" => ColorChoice::Never,
+            _ => {
+                eprintln!("{} --color expects 'always', 'auto' or 'never', found '{}'",
+                          "Invalid option:".paint("255,71,71"),
+                          when);
+                process::exit(1);
+            }
+        };
+        set_color_choice(choice);
+    }
+
+    rest
+}
 
-    let ast = parse(&tokens).expect("Failed to parse Loa code");
+#[derive(Default)]
+struct RunOptions {
+    file_path: Option<String>,
+    dump_ast: bool,
+    dump_tokens: bool,
+    seed: Option<u64>,
+    stdin_file: Option<String>,
+    require_semicolons: bool,
+    config: Option<Json>,
+}
 
-    // println!("code: \n{}\n", code);
+impl RunOptions {
+    // Flags may appear before or after the file path
+    fn parse(args: &[String]) -> Self {
+        let mut options = RunOptions::default();
 
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--ast" => options.dump_ast = true,
+                "--tokens" => options.dump_tokens = true,
+                "--require-semicolons" => options.require_semicolons = true,
+                "--seed" => match args.next().and_then(|seed| seed.parse().ok()) {
+                    Some(seed) => options.seed = Some(seed),
+                    None => {
+                        eprintln!("{} --seed expects a non-negative integer",
+                                  "Invalid option:".paint("255,71,71"));
+                        process::exit(1);
+                    }
+                },
+                // Parsed here so malformed JSON is reported before anything runs
+                "--args-json" => match args.next().map(|json| parse_json(json)) {
+                    Some(Ok(config)) => options.config = Some(config),
+                    Some(Err(err)) => {
+                        eprintln!("{} --args-json is not valid JSON: {}",
+                                  "Invalid option:".paint("255,71,71"),
+                                  err);
+                        process::exit(1);
+                    }
+                    None => {
+                        eprintln!("{} --args-json expects a JSON value",
+                                  "Invalid option:".paint("255,71,71"));
+                        process::exit(1);
+                    }
+                },
+                "--stdin-file" => match args.next() {
+                    Some(name) => options.stdin_file = Some(name.clone()),
+                    None => {
+                        eprintln!("{} --stdin-file expects a file name",
+                                  "Invalid option:".paint("255,71,71"));
+                        process::exit(1);
+                    }
+                },
+                flag if flag.starts_with("--") => {
+                    eprintln!("{} {}",
+                              "Unknown option:".paint("255,71,71"),
+                              flag);
+                    process::exit(1);
+                }
+                _ if options.file_path.is_none() => options.file_path = Some(arg.clone()),
+                _ => {
+                    eprintln!("{} {}",
+                              "Unexpected argument:".paint("255,71,71"),
+                              arg);
+                    process::exit(1);
+                }
+            }
+        }
 
-    // println!("AST:\n{:#?}", ast);
+        options
+    }
+}
+
+unsafe fn run_loa_file(file_path: &str, options: &RunOptions) {
+    // `-` reads the program from stdin, e.g. when an editor pipes a buffer
+    let code = if file_path == "-" {
+        let mut code = String::new();
+        io::stdin().read_to_string(&mut code).expect("Failed to read stdin");
+        set_current_file(options.stdin_file.as_deref().unwrap_or("<stdin>"));
+        code
+    } else {
+        set_current_file(file_path);
+        fs::read_to_string(file_path).expect("Failed to read file")
+    };
+
+    run_source(&code, options);
+}
+
+fn run_source(code: &str, options: &RunOptions) {
+    set_require_semicolons(options.require_semicolons);
+    set_current_source(code);
+
+    let mut lexer = Lexer::new(code);
+    let tokens = lexer.tokenize();
+
+    if options.dump_tokens {
+        println!("{:<24} {:<20} Line:Col", "Token Type", "Lexeme");
+        println!("{}", "=".repeat(56));
+        for token in &tokens {
+            println!("{:<24} {:<20} {}:{}", token.token_type.to_string(), token.lexeme, token.line, token.column);
+        }
+        return;
+    }
+
+    let ast = match parse(&tokens) {
+        Ok(ast) => ast,
+        Err(errors) => {
+            report_parse_errors(&errors);
+            process::exit(1);
+        }
+    };
+
+    if options.dump_ast {
+        println!("{:#?}", ast);
+        return;
+    }
 
     let mut interpreter = Interpreter::new();
+    interpreter.set_version(VERSION);
+    watch_interrupts(&interpreter);
+    if let Some(seed) = options.seed {
+        interpreter.set_seed(seed);
+    }
+    if let Some(config) = &options.config {
+        interpreter.set_config(config.clone());
+    }
     interpreter.execute(&ast);
 }
 
+fn check_loa_file(file_path: &str, options: &RunOptions) {
+    let code = fs::read_to_string(file_path).expect("Failed to read file");
+    set_current_file(file_path);
+    set_current_source(code.as_str());
+    set_require_semicolons(options.require_semicolons);
+
+    let mut lexer = Lexer::new(&code);
+    let tokens = lexer.tokenize();
+
+    if let Err(errors) = parse(&tokens) {
+        report_parse_errors(&errors);
+        process::exit(1);
+    }
+
+    println!("{} {}",
+             "OK:".paint("2,161,47"),
+             file_path);
+}
+
+fn report_parse_errors(errors: &[LoaError]) {
+    for error in errors {
+        error.display();
+    }
+    if errors.len() > 1 {
+        eprintln!("{} {} errors",
+                  "Aborting due to".paint("255,71,71"),
+                  errors.len());
+    }
+}
+
+// Ctrl-C stops the running program instead of killing the process, so the
+// REPL can return to its prompt
+fn watch_interrupts(interpreter: &Interpreter) {
+    let interrupted = interpreter.interrupt_handle();
+    let result = ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst));
+    if let Err(err) = result {
+        eprintln!("{} {}",
+                  "Could not install the Ctrl-C handler:".paint("255,71,71"),
+                  err);
+    }
+}
 
 fn repl_mode() {
-    use std::io::{self, Write};
+    use std::io::Write;
 
     let mut interpreter = Interpreter::new();
+    interpreter.set_version(VERSION);
+    watch_interrupts(&interpreter);
+    set_current_file("<repl>");
 
+    // One lexer serves every line: `reset` hands its buffers to the next
+    let mut lexer = Lexer::new("");
     loop {
         print!("Loa > ");
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        if io::stdin().read_line(&mut input).unwrap() == 0 {
+            break;
+        }
         let trimmed = input.trim();
 
         if trimmed == "exit" || trimmed == "quit" {
             break;
         }
 
-        let mut lexer = Lexer::new(trimmed);
-        let tokens = lexer.tokenize();
+        if let Some(topic) = trimmed.strip_prefix(".help") {
+            print_builtin_help(topic.trim());
+            continue;
+        }
+
+        // Each entry is its own one-line source for error snippets
+        set_current_source(trimmed);
+        let mut line_lexer = lexer.reset(trimmed);
+        let tokens = line_lexer.tokenize();
+        lexer = line_lexer.reset("");
 
-        if tokens.is_empty() {
+        // Blank and comment-only lines lex to just the end of input
+        if let [Token { token_type: TokenType::Eof, .. }] = tokens.as_slice() {
             continue;
         }
 
         match parse(&tokens) {
-            Some(ast) => {
-                interpreter.execute(&ast);
-            }
-            None => {
-                println!("Parse error: failed to parse input.");
+            Ok(ast) => {
+                // A lone expression echoes its value, like a Python shell
+                if let [ASTNode::Statement(StatementNode::Expression(expr))] = ast.as_slice() {
+                    match interpreter.evaluate(expr) {
+                        Ok(Value::None) => {}
+                        Ok(value) => println!("{}", value),
+                        Err(error) => error.display(),
+                    }
+                } else {
+                    interpreter.execute(&ast);
+                }
             }
+            Err(errors) => report_parse_errors(&errors),
+        }
+    }
+}
+
+// `.help` lists every built-in, `.help <name>` describes one
+fn print_builtin_help(name: &str) {
+    if name.is_empty() {
+        for builtin in BUILTINS {
+            println!("  {} {}",
+                     format!("{:<24}", builtin.signature).paint("38,139,235"),
+                     builtin.description);
         }
+        return;
+    }
+
+    match BUILTINS.iter().find(|builtin| builtin.name == name) {
+        Some(builtin) => println!("{}  {}",
+                                  builtin.signature.paint("38,139,235"),
+                                  builtin.description),
+        None => eprintln!("{} {}",
+                          "No built-in named".paint("255,71,71"),
+                          name),
     }
 }
@@ -0,0 +1,307 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn loa() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_loa"));
+    command.env_remove("NO_COLOR");
+    command
+}
+
+fn run(command: &mut Command, stdin: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start loa");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn ast_prints_the_tree_instead_of_running() {
+    let output = run(loa().args(["run", "-", "--ast"]), "x = 1 + 2\nprint(\"ran\")\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let tree = stdout(&output);
+    assert!(tree.contains("Assign {") && tree.contains("operator: Add"), "{}", tree);
+    assert!(!tree.lines().any(|line| line == "ran"), "the program ran: {}", tree);
+}
+
+#[test]
+fn ast_reports_parse_errors() {
+    let output = run(loa().args(["run", "-", "--ast"]), "x = (1 +\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).starts_with("error: Expected an expression"), "{}", stderr(&output));
+}
+
+#[test]
+fn eval_runs_the_given_code() {
+    for command in ["-e", "eval"] {
+        let output = run(loa().args([command, "x = 6\nprint(x * 7)"]), "");
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(stdout(&output), "42\n");
+    }
+}
+
+#[test]
+fn eval_reports_errors_against_eval() {
+    let output = run(loa().args(["-e", "print(1 +"]), "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("--> <eval>:1:10"), "{}", stderr(&output));
+
+    let output = run(loa().args(["-e", "print(1 / 0)"]), "");
+    assert!(stderr(&output).starts_with("error[DivideByZero]: Division by zero\n  --> <eval>:1:9"), "{}", stderr(&output));
+}
+
+#[test]
+fn eval_needs_code() {
+    let output = run(loa().arg("-e"), "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("loa -e <code>"), "{}", stderr(&output));
+
+    let output = run(loa().args(["-e", "print(1)", "extra"]), "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Unexpected argument: extra"), "{}", stderr(&output));
+}
+
+#[test]
+fn stdin_file_names_the_piped_program_in_errors() {
+    let output = run(loa().args(["run", "-", "--stdin-file", "buffer.loa"]), "print(\"ok\")\nprint(1 / 0)\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("ok\n"), "{}", stdout(&output));
+    assert!(stderr(&output).contains("--> buffer.loa:2:9"), "{}", stderr(&output));
+
+    // Without it, the program is called <stdin>
+    let output = run(loa().args(["run", "-"]), "print(1 / 0)\n");
+    assert!(stderr(&output).contains("--> <stdin>:1:9"), "{}", stderr(&output));
+}
+
+#[test]
+fn stdin_file_needs_a_name() {
+    let output = run(loa().args(["run", "-", "--stdin-file"]), "print(1)\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("--stdin-file expects a file name"), "{}", stderr(&output));
+}
+
+const ESCAPE: &str = "\x1b[";
+
+#[test]
+fn color_always_colors_stdout_and_stderr() {
+    for args in [&["--color=always", "-e", "print(1 / 0)"][..], &["-e", "print(1 / 0)", "--color", "always"]] {
+        let output = run(loa().args(args), "");
+        assert!(stderr(&output).starts_with("\x1b[38;2;255;71;71merror[DivideByZero]:"), "{:?}", stderr(&output));
+    }
+
+    let output = run(loa().args(["--version", "--color=always"]), "");
+    assert!(stdout(&output).starts_with(ESCAPE), "{:?}", stdout(&output));
+}
+
+#[test]
+fn color_never_and_auto_stay_plain_when_piped() {
+    for flag in ["--color=never", "--color=auto"] {
+        let output = run(loa().args([flag, "-e", "print(1 / 0)"]), "");
+        assert!(!stderr(&output).contains(ESCAPE), "{}: {:?}", flag, stderr(&output));
+        assert!(stderr(&output).starts_with("error[DivideByZero]:"), "{:?}", stderr(&output));
+    }
+}
+
+#[test]
+fn color_rejects_unknown_choices() {
+    let output = run(loa().args(["--color=sometimes", "--version"]), "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("--color expects 'always', 'auto' or 'never', found 'sometimes'"), "{}", stderr(&output));
+}
+
+#[test]
+fn no_color_turns_coloring_off() {
+    let output = run(loa().args(["--no-color", "-e", "print(1 / 0)"]), "");
+    assert!(stderr(&output).starts_with("error[DivideByZero]:"), "{:?}", stderr(&output));
+
+    // The last color option wins
+    let output = run(loa().args(["--color=always", "--no-color", "--version"]), "");
+    assert!(!stdout(&output).contains(ESCAPE), "{:?}", stdout(&output));
+    let output = run(loa().args(["--no-color", "--color=always", "--version"]), "");
+    assert!(stdout(&output).starts_with(ESCAPE), "{:?}", stdout(&output));
+}
+
+// Runs `loa -e 'print(1 / 0)'` on a pseudo-terminal through util-linux
+// `script`, so 'auto' coloring applies; None when `script` is unavailable
+#[cfg(target_os = "linux")]
+fn on_terminal(no_color: bool) -> Option<String> {
+    let mut command = Command::new("script");
+    command.args(["-qec", &format!("'{}' -e 'print(1 / 0)'", env!("CARGO_BIN_EXE_loa")), "/dev/null"]);
+    if no_color {
+        command.env("NO_COLOR", "1");
+    } else {
+        command.env_remove("NO_COLOR");
+    }
+    let output = command.stdin(Stdio::null()).output().ok()?;
+    output.status.success().then(|| stdout(&output))
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn no_color_variable_turns_auto_coloring_off_on_a_terminal() {
+    let Some(colored) = on_terminal(false) else { return };
+    assert!(colored.starts_with(ESCAPE), "{:?}", colored);
+
+    let plain = on_terminal(true).unwrap();
+    assert!(plain.starts_with("error[DivideByZero]:"), "{:?}", plain);
+}
+
+#[test]
+fn no_color_variable_is_overridden_by_an_explicit_choice() {
+    let output = run(loa().env("NO_COLOR", "1").args(["--color=always", "-e", "print(1 / 0)"]), "");
+    assert!(stderr(&output).starts_with(ESCAPE), "{:?}", stderr(&output));
+}
+
+// Reads the child's stdout until `expected` has appeared
+#[cfg(unix)]
+fn read_until(stdout: &mut impl std::io::Read, seen: &mut String, expected: &str) {
+    let mut buf = [0; 256];
+    while !seen.contains(expected) {
+        let n = stdout.read(&mut buf).unwrap();
+        assert!(n > 0, "loa exited before printing {:?}: {:?}", expected, seen);
+        seen.push_str(&String::from_utf8_lossy(&buf[..n]));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_stops_a_loop_and_the_repl_carries_on() {
+    let mut child = loa()
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start loa");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut child_stdout = child.stdout.take().unwrap();
+    let mut seen = String::new();
+
+    // One line, so the loop is already running once "looping" is printed
+    stdin.write_all(b"print(\"looping\"); while (true): { x = 1 }\n").unwrap();
+    read_until(&mut child_stdout, &mut seen, "looping\n");
+
+    let status = Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    assert!(status.success());
+
+    stdin.write_all(b"print(\"back at the prompt\")\n").unwrap();
+    read_until(&mut child_stdout, &mut seen, "back at the prompt\n");
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(stderr(&output).starts_with("error[Interrupted]: Execution interrupted"), "{:?}", stderr(&output));
+}
+
+#[test]
+fn require_semicolons_reports_the_end_of_the_statement() {
+    let source = "x = 1;\ny = 2\nif (x < y):\n    print(x);\n    print(y)\n";
+
+    // Without the option `;` stays optional
+    let output = run(loa().args(["run", "-"]), source);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "1\n2\n");
+
+    let output = run(loa().args(["run", "-", "--require-semicolons"]), source);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    let errors = stderr(&output);
+    assert!(errors.contains("error: Expected ';' after statement, found end of line\n  --> <stdin>:2:6\n"), "{}", errors);
+    // The last statement of a block is reported on its own line, not at the Dedent
+    assert!(errors.contains("error: Expected ';' after statement, found end of line\n  --> <stdin>:5:13\n"), "{}", errors);
+
+    let output = run(loa().args(["-e", "print(1)", "--require-semicolons"]), "");
+    assert!(stderr(&output).contains("found end of input\n  --> <eval>:1:9\n"), "{}", stderr(&output));
+}
+
+#[test]
+fn repl_echoes_expressions_but_not_assignments() {
+    let output = run(loa().arg("repl"), "40 + 2\nx = 1\nx\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).replace("Loa > ", ""), "42\n1\n");
+}
+
+#[test]
+fn tokens_lists_each_token_with_its_position() {
+    let output = run(loa().args(["run", "-", "--tokens"]), "if (x):\n    y = 1\nz\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    let lines: Vec<String> = stdout(&output)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+
+    for expected in ["'if' if 1:1", "identifier 'x' x 1:5", "end of line 1:8", "indentation 2:5", "number 1 1 2:9", "end of block 3:1", "end of input 4:1"] {
+        assert!(lines.iter().any(|line| line == expected), "no {:?} in {:#?}", expected, lines);
+    }
+}
+
+// Writes `source` to a file under the test's temporary directory
+fn script(name: &str, source: &str) -> String {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, source).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn check_parses_without_running() {
+    let path = script("check_ok.loa", "print(\"ran\")\nx = 1 / 0\n");
+    let output = run(loa().args(["check", &path]), "");
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), format!("OK: {}\n", path));
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn check_reports_parse_errors() {
+    let path = script("check_missing_colon.loa", "if (true)\n    print(1)\n");
+    let output = run(loa().args(["check", &path]), "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).starts_with(&format!("error: Expected ':' after 'if' condition\n  --> {}:1:10\n", path)), "{}", stderr(&output));
+}
+
+#[test]
+fn args_json_is_read_with_config() {
+    let json = r#"{"server": {"ports": [8080, 8081]}, "face": "\ud83d\ude00!"}"#;
+    let output = run(loa().args(["-e", "print(config(\"server.ports.0\"))\nprint(config(\"face\"), len(config(\"face\")))", "--args-json", json]), "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    // The surrogate pair decodes to one character
+    assert_eq!(stdout(&output), "8080\n\u{1F600}! 2\n");
+}
+
+#[test]
+fn args_json_rejects_malformed_json() {
+    let output = run(loa().args(["-e", "print(\"ran\")", "--args-json", r#"{"server": "#]), "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("--args-json is not valid JSON: unexpected end of input"), "{}", stderr(&output));
+}
+
+#[test]
+fn repl_help_shows_a_builtins_signature() {
+    let output = run(loa().arg("repl"), ".help len\n.help nope\n");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("len(x)  Characters in a string, elements in an array or entries in a map\n"), "{}", stdout(&output));
+    assert!(!stdout(&output).contains("nope"), "{}", stdout(&output));
+    assert_eq!(stderr(&output), "No built-in named nope\n");
+}
+
+#[test]
+fn errors_at_the_end_of_input_quote_the_last_line() {
+    let output = run(loa().args(["run", "-"]), "print(1)\nx = (1 +\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "error: Expected an expression, found end of input\n  --> <stdin>:2:9\n   |\n  2 | x = (1 +\n   |         ^\n");
+}